@@ -0,0 +1,204 @@
+//! This module implements validation of the anchors of the generated
+//! documentation, and of the intra-document links pointing to them.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    commonmark::{DocFormat, ManualEntry},
+    format::{inline_code_spans, CodeFences, Inline},
+    lint::{Diagnostic, Lint},
+    location::SourceLocation,
+};
+
+/// A rendered category, as validated by [validate_anchors].
+pub struct Document<'a> {
+    /// The file the category was collected from.
+    pub file: Option<&'a Path>,
+
+    /// The rendered heading and description of the category, see
+    /// [crate::retrieve_description].
+    pub description: &'a str,
+
+    pub entries: &'a [ManualEntry],
+}
+
+/// An anchor and where it is defined.
+struct Anchor {
+    /// What defines the anchor, e.g. the title of an entry.
+    origin: String,
+    location: SourceLocation,
+}
+
+/// Validate the anchors of a set of documents, which are rendered with
+/// `anchor_prefix`.
+///
+/// The anchors of all entries, their examples, and those written in
+/// descriptions (e.g. `# Examples {#ex-foo}` or `::: {.example #ex-foo}`)
+/// must be unique. Collisions are reported as errors, e.g. of `foo'` and
+/// `foo-prime`, which share the anchor `foo-prime`.
+///
+/// Links in descriptions to anchors of the documents (e.g.
+/// `[foo](#function-library-lib.strings.foo)`) which are not defined are
+/// reported as warnings. If the documents are not the whole library,
+/// `link_scope` is the start of the anchors which they define, e.g.
+/// `function-library-lib.strings.`, and only links to such anchors are
+/// checked.
+pub fn validate_anchors(
+    documents: &[Document],
+    anchor_prefix: &str,
+    link_scope: Option<&str>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut anchors: HashMap<String, Anchor> = HashMap::new();
+    let mut define = |id: String, origin: String, location: &SourceLocation| {
+        if let Some(existing) = anchors.get(&id) {
+            diagnostics.push(Diagnostic {
+                lint: Lint::DuplicateAnchor,
+                location: location.clone(),
+                message: format!(
+                    "anchor `{}` of {} is already defined by {} at {}",
+                    id,
+                    origin,
+                    existing.origin,
+                    existing.location.display_position()
+                ),
+            });
+        } else {
+            anchors.insert(
+                id,
+                Anchor {
+                    origin,
+                    location: location.clone(),
+                },
+            );
+        }
+    };
+
+    // Links are collected first, and checked once all anchors are known.
+    let mut links = vec![];
+    for document in documents {
+        let location = file_location(document.file);
+        for id in explicit_anchors(document.description) {
+            define(id, "the category description".to_string(), &location);
+        }
+        links.extend(
+            link_targets(document.description)
+                .into_iter()
+                .map(|target| (target, location.clone())),
+        );
+
+        for entry in document.entries {
+            let (ident, title) = entry.get_ident_title();
+            let location = entry
+                .span
+                .clone()
+                .unwrap_or_else(|| file_location(document.file));
+            let origin = format!("`{}`", title);
+
+            define(
+                format!("{}{}", anchor_prefix, ident),
+                origin.clone(),
+                &location,
+            );
+            if entry.format == DocFormat::Legacy && entry.example.is_some() {
+                define(
+                    format!("{}example-{}", anchor_prefix, ident),
+                    format!("the example of {}", origin),
+                    &location,
+                );
+            }
+
+            let description = entry.description.join("\n\n");
+            for id in explicit_anchors(&description) {
+                define(id, format!("the description of {}", origin), &location);
+            }
+            links.extend(
+                link_targets(&description)
+                    .into_iter()
+                    .map(|target| (target, location.clone())),
+            );
+        }
+    }
+
+    for (target, location) in links {
+        let checked = link_scope.is_none_or(|scope| target.starts_with(scope));
+        if checked && !anchors.contains_key(&target) {
+            diagnostics.push(Diagnostic {
+                lint: Lint::BrokenLink,
+                location,
+                message: format!("link to `#{}` does not resolve to a known anchor", target),
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// The location of a whole file, i.e. its beginning.
+fn file_location(file: Option<&Path>) -> SourceLocation {
+    SourceLocation {
+        path: file.map(Path::to_path_buf),
+        range: 0..0,
+        line: 1,
+        column: 1,
+    }
+}
+
+/// Returns the lines of a Markdown document which are not part of a
+/// fenced code block, without their inline code spans.
+fn prose_lines(markdown: &str) -> impl Iterator<Item = String> + '_ {
+    let mut fences = CodeFences::default();
+    markdown
+        .lines()
+        .filter(move |line| !fences.update(line))
+        .map(|line| {
+            inline_code_spans(line)
+                .into_iter()
+                .filter_map(|part| match part {
+                    Inline::Text(text) => Some(text),
+                    Inline::Code { .. } => None,
+                })
+                .collect()
+        })
+}
+
+/// Returns the ids of attribute blocks, e.g. `ex-foo` in `# Example {#ex-foo}`
+/// or `::: {.example #ex-foo}`.
+fn explicit_anchors(markdown: &str) -> Vec<String> {
+    let mut ids = vec![];
+    for line in prose_lines(markdown) {
+        let mut rest = line.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let attributes = &rest[start + 1..start + end];
+            ids.extend(
+                attributes
+                    .split_whitespace()
+                    .filter_map(|attribute| attribute.strip_prefix('#'))
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string),
+            );
+            rest = &rest[start + end + 1..];
+        }
+    }
+    ids
+}
+
+/// Returns the targets of links to anchors, e.g. `foo` in `[text](#foo)`.
+fn link_targets(markdown: &str) -> Vec<String> {
+    let mut targets = vec![];
+    for line in prose_lines(markdown) {
+        let mut rest = line.as_str();
+        while let Some(start) = rest.find("](#") {
+            rest = &rest[start + 3..];
+            // The target ends at the closing parenthesis or an optional title.
+            let end = rest.find([')', ' ']).unwrap_or(rest.len());
+            targets.push(rest[..end].to_string());
+            rest = &rest[end..];
+        }
+    }
+    targets
+}
@@ -0,0 +1,84 @@
+//! This module implements the on-disk cache of `nixdoc build`, which
+//! stores the rendered documentation of files so that unchanged files
+//! are not parsed and rendered again.
+//!
+//! Entries are keyed by a hash of everything their documentation
+//! depends on: the version of nixdoc, the contents of the file and of the
+//! files it imports, and the options it is rendered with, see [Cache::key]. Changing any of them
+//! changes the key, so entries are never invalidated, only left unused.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::NixdocError;
+
+/// A directory of cached documentation.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+
+    /// Counter for the names of temporary files, which are renamed once
+    /// written so that entries are never read partially written.
+    temporary: AtomicUsize,
+}
+
+impl Cache {
+    /// Open the cache in a directory, which is created if it does not
+    /// exist. With `clean`, all cached entries are discarded first.
+    pub fn open(dir: &Path, clean: bool) -> Result<Cache, NixdocError> {
+        let io_error = |source| NixdocError::Io {
+            path: dir.to_path_buf(),
+            source,
+        };
+        if clean {
+            match fs::remove_dir_all(dir) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    return Err(io_error(error))
+                }
+                _ => {}
+            }
+        }
+        fs::create_dir_all(dir).map_err(io_error)?;
+        Ok(Cache {
+            dir: dir.to_path_buf(),
+            temporary: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the key of documentation depending on the given parts, e.g.
+    /// the contents of a file and the options it is rendered with.
+    pub fn key(parts: &[&[u8]]) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        for part in parts {
+            // The length separates the parts, so that e.g. `ab` and `c`
+            // have another key than `a` and `bc`.
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Returns the cached documentation of a key, if there is any.
+    pub fn get(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.dir.join(key)).ok()
+    }
+
+    /// Store the documentation of a key.
+    pub fn put(&self, key: &str, text: &str) -> Result<(), NixdocError> {
+        let temporary = self.dir.join(format!(
+            "{}.{}.{}.tmp",
+            key,
+            std::process::id(),
+            self.temporary.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temporary, text).map_err(|source| NixdocError::Io {
+            path: temporary.clone(),
+            source,
+        })?;
+        let path = self.dir.join(key);
+        fs::rename(&temporary, &path).map_err(|source| NixdocError::Io { path, source })
+    }
+}
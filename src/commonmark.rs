@@ -16,7 +16,14 @@
 //! This module implements CommonMark output for a struct
 //! representing a single entry in the manual.
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+use crate::{
+    format::{self, single_line_expression, CodeFences},
+    location::SourceLocation,
+    sections::CodeBlock,
+};
 
 /// Represent a single function argument name and its (optional)
 /// doc-string.
@@ -24,6 +31,38 @@ use serde::Serialize;
 pub struct SingleArg {
     pub name: String,
     pub doc: Option<String>,
+
+    /// Source text of the default value of a pattern entry, e.g. `"1.0"`
+    /// in `{ version ? "1.0" }`.
+    #[serde(skip)]
+    pub default: Option<String>,
+
+    /// Whether the argument may be omitted, i.e. is an entry of a pattern
+    /// with a default value.
+    #[serde(skip)]
+    pub optional: bool,
+}
+
+impl SingleArg {
+    /// The term of the argument in a definition list, e.g. `version ? "1.0"`.
+    /// Default values which cannot be put on a single line are left out, see
+    /// [SingleArg::multiline_default].
+    pub(crate) fn term(&self) -> String {
+        match self.default.as_deref().and_then(single_line_expression) {
+            Some(default) => format!("{} ? {}", self.name, default),
+            None => self.name.clone(),
+        }
+    }
+
+    /// The default value of the argument if it spans several lines, e.g. a
+    /// multi-line string, which is rendered verbatim as a code block.
+    pub(crate) fn multiline_default(&self) -> Option<String> {
+        let default = self.default.as_deref()?;
+        match single_line_expression(default) {
+            Some(_) => None,
+            None => format::handle_indentation(default),
+        }
+    }
 }
 
 /// Represent a function argument, which is either a flat identifier
@@ -33,47 +72,92 @@ pub enum Argument {
     /// Flat function argument (e.g. `n: n * 2`).
     Flat(SingleArg),
 
-    /// Pattern function argument (e.g. `{ name, age }: ...`)
-    Pattern(Vec<SingleArg>),
+    /// Pattern function argument (e.g. `{ name, age, ... }: ...`)
+    #[serde(serialize_with = "serialize_pattern")]
+    Pattern {
+        entries: Vec<SingleArg>,
+
+        /// Whether the pattern accepts other attributes, i.e. ends in `...`.
+        ellipsis: bool,
+
+        /// Name the whole attribute set is bound to, e.g. `args` in
+        /// `args@{ name, ... }`.
+        bind: Option<String>,
+    },
+}
+
+/// Serialize a pattern as the list of its entries, which keeps the JSON
+/// output of version 1 unchanged.
+fn serialize_pattern<S: Serializer>(
+    entries: &[SingleArg],
+    _ellipsis: &bool,
+    _bind: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    entries.serialize(serializer)
 }
 
 impl Argument {
     /// Write CommonMark structure for a single function argument.
     /// We use the definition list extension, which prepends each argument with `: `.
     /// For pattern arguments, we create a nested definition list.
-    fn format_argument(self) -> String {
+    pub(crate) fn format_argument(self) -> String {
         match self {
             // Write a flat argument entry, e.g. `id = x: x`
             //
             // `x`
             // : Function argument
+            //
+            // Entries of patterns with a default value are written as in
+            // Nix, e.g. `` `version ? "1.0"` ``. Defaults spanning several
+            // lines follow the description in a code block.
             Argument::Flat(arg) => {
+                let mut doc = arg.doc.clone().unwrap_or("Function argument".into());
+                if let Some(default) = arg.multiline_default() {
+                    doc = format!("{}\n\nDefault:\n\n```nix\n{}\n```", doc.trim(), default);
+                }
                 format!(
                     "`{}`\n\n: {}\n\n",
-                    arg.name,
-                    handle_indentation(arg.doc.unwrap_or("Function argument".into()).trim())
+                    arg.term(),
+                    handle_indentation(doc.trim())
                 )
             }
 
             // Write a pattern argument entry and its individual
             // parameters as a nested structure, e.g.:
             //
-            // `foo = { a }: a`
+            // `foo = { a, ... }: a`
             //
             // structured function argument
             // : `a`
             //   : Function argument
-            Argument::Pattern(pattern_args) => {
+            //
+            //   `...`
+            //   : Other attributes are accepted too
+            Argument::Pattern {
+                entries,
+                ellipsis,
+                bind,
+            } => {
                 let mut inner = String::new();
-                for pattern_arg in pattern_args {
+                for pattern_arg in entries {
                     inner += &Argument::Flat(pattern_arg).format_argument();
                 }
+                if ellipsis {
+                    inner += "`...`\n\n: Other attributes are accepted too\n\n";
+                }
 
                 let indented = textwrap::indent(&inner, "  ");
 
+                // Patterns bound with `@` are named, e.g. `args@{ a }: a`.
+                let bound = match bind {
+                    Some(name) => format!(", bound to `{}`", name),
+                    None => String::new(),
+                };
                 format!(
                     // The `:` creates another definition list of which `indented` is the term.
-                    "structured function argument\n\n: {}",
+                    "structured function argument{}\n\n: {}",
+                    bound,
                     // drop leading indentation on the first line, the `: ` serves this function
                     // already.
                     indented.trim_start()
@@ -99,27 +183,43 @@ fn handle_indentation(raw: &str) -> String {
 
 /// Generate the identifier for CommonMark.
 /// ident is used as URL Encoded link to the function and has thus stricter rules (i.e. "' " in "lib.map' "  is not allowed).
-pub(crate) fn get_identifier(prefix: &String, category: &String, name: &String) -> String {
+pub(crate) fn get_identifier(prefix: &str, category: &str, name: &str) -> String {
     let name_prime = name.replace('\'', "-prime");
-    vec![prefix, category, &name_prime]
+    [prefix, category, &name_prime]
         .into_iter()
         .filter(|x| !x.is_empty())
-        .cloned()
-        .collect::<Vec<String>>()
+        .collect::<Vec<&str>>()
         .join(".")
 }
 
 /// Generate the title for CommonMark.
 /// the title is the human-readable name of the function.
-pub(crate) fn get_title(prefix: &String, category: &String, name: &String) -> String {
-    vec![prefix, category, name]
+pub(crate) fn get_title(prefix: &str, category: &str, name: &str) -> String {
+    [prefix, category, name]
         .into_iter()
         .filter(|x| !x.is_empty())
-        .cloned()
-        .collect::<Vec<String>>()
+        .collect::<Vec<&str>>()
         .join(".")
 }
 
+/// Serialize an attribute path as its dot-separated name, which keeps the
+/// JSON output compatible with the single `name` it used to contain.
+fn serialize_attr_path<S: Serializer>(attr_path: &[String], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&attr_path.join("."))
+}
+
+/// The comment format an entry is documented with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum DocFormat {
+    /// Legacy `/* */` comments with `Type:` and `Example:` lines.
+    #[default]
+    Legacy,
+
+    /// RFC145 `/** */` doc-comments. Their description contains all
+    /// sections, which are only parsed for structured output.
+    Rfc145,
+}
+
 /// Represents a single manual section describing a library function.
 #[derive(Clone, Debug, Serialize)]
 pub struct ManualEntry {
@@ -132,8 +232,17 @@ pub struct ManualEntry {
     /// Location of the function.
     pub location: Option<String>,
 
-    /// Name of the section (used as the title).
-    pub name: String,
+    /// Attribute path of the entry relative to the category, e.g.
+    /// `["foo", "bar"]` for `lib.category.foo.bar`. Joined with `.` it is
+    /// used as the title of the section.
+    #[serde(rename = "name", serialize_with = "serialize_attr_path")]
+    pub attr_path: Vec<String>,
+
+    /// Number of documented entries enclosing this one in nested
+    /// attribute sets. Top-level entries have a depth of 0 and are
+    /// rendered as H2, nested entries one heading level deeper each.
+    #[serde(skip)]
+    pub depth: usize,
 
     /// Type signature (if provided). This is not actually a checked
     /// type signature in any way.
@@ -146,17 +255,151 @@ pub struct ManualEntry {
     /// Usage example for the entry.
     pub example: Option<String>,
 
+    /// All code blocks of the `# Examples` section of a doc-comment,
+    /// including their info strings.
+    #[serde(skip)]
+    pub examples: Vec<CodeBlock>,
+
+    /// Sections of a doc-comment other than `# Type`, `# Arguments` and
+    /// `# Examples`, keyed by their heading.
+    #[serde(skip)]
+    pub sections: BTreeMap<String, String>,
+
+    /// The comment format the entry is documented with.
+    #[serde(skip)]
+    pub format: DocFormat,
+
     /// Arguments of the function.
     pub args: Vec<Argument>,
+
+    /// Whether the arguments are documented by an `# Arguments` section of
+    /// the description, rather than collected from the lambda.
+    #[serde(skip)]
+    pub arguments_section: bool,
+
+    /// Source of the `inherit (source)` clause this entry is re-exported
+    /// with, if any: the attribute path of the source (e.g. `lib.strings`)
+    /// or otherwise its expression on a single line.
+    #[serde(skip)]
+    pub inherited_from: Option<String>,
+
+    /// Location of the binding in the Nix source it was collected from.
+    #[serde(skip)]
+    pub span: Option<SourceLocation>,
 }
 
 impl ManualEntry {
+    /// The attribute path of the entry joined with `.`.
+    pub fn name(&self) -> String {
+        self.attr_path.join(".")
+    }
+
     pub(crate) fn get_ident_title(&self) -> (String, String) {
-        let ident = get_identifier(&self.prefix, &self.category, &self.name);
-        let title = get_title(&self.prefix, &self.category, &self.name);
+        let name = self.name();
+        let ident = get_identifier(&self.prefix, &self.category, &name);
+        let title = get_title(&self.prefix, &self.category, &name);
         (ident, title)
     }
 
+    /// Returns the names the entry can be referred to by: its title (e.g.
+    /// `lib.strings.splitString`), its name qualified with the category
+    /// (`strings.splitString`) or the prefix (`lib.splitString`), and its
+    /// name alone (`splitString`).
+    pub(crate) fn names(&self) -> [String; 4] {
+        let name = self.name();
+        [
+            get_title(&self.prefix, &self.category, &name),
+            get_title("", &self.category, &name),
+            get_title(&self.prefix, "", &name),
+            name,
+        ]
+    }
+
+    /// Returns the description up to its first heading, i.e. without the
+    /// sections of doc-comments.
+    pub(crate) fn prose(&self) -> String {
+        let description = self.description.join("\n\n");
+        let mut fences = CodeFences::default();
+        let mut prose = String::new();
+        for line in description.lines() {
+            if !fences.update(line) && line.starts_with('#') {
+                break;
+            }
+            prose.push_str(line);
+            prose.push('\n');
+        }
+        prose
+    }
+
+    /// Whether the arguments are rendered after the description.
+    ///
+    /// The arguments of doc-comments are only rendered if they have
+    /// doc-comments of their own, and are not already documented by the
+    /// description.
+    pub(crate) fn renders_args(&self) -> bool {
+        let documented_args = !self.arguments_section
+            && self.args.iter().any(|arg| match arg {
+                Argument::Flat(arg) => arg.doc.is_some(),
+                Argument::Pattern { entries, .. } => entries.iter().any(|arg| arg.doc.is_some()),
+            });
+        (self.format == DocFormat::Legacy || documented_args) && !self.args.is_empty()
+    }
+
+    /// Whether formats which render the description without its sections
+    /// (see [ManualEntry::prose]) list the arguments: those rendered by
+    /// [ManualEntry::renders_args], and those documented by an
+    /// `# Arguments` section, which is not part of the prose.
+    pub(crate) fn lists_args(&self) -> bool {
+        self.renders_args() || (self.arguments_section && !self.args.is_empty())
+    }
+
+    /// The code blocks of the usage examples. Legacy comments have at most
+    /// one example, without info string.
+    pub(crate) fn example_blocks(&self) -> Vec<CodeBlock> {
+        match self.format {
+            DocFormat::Legacy => self
+                .example
+                .iter()
+                .map(|code| CodeBlock {
+                    info: None,
+                    code: code.clone(),
+                })
+                .collect(),
+            DocFormat::Rfc145 => self.examples.clone(),
+        }
+    }
+
+    /// The arguments to render. Of the arguments collected from the lambda
+    /// of a doc-comment, only those with doc-comments of their own are
+    /// rendered, the others are left to the description.
+    pub(crate) fn rendered_args(&self) -> Vec<Argument> {
+        if self.format == DocFormat::Legacy || self.arguments_section {
+            return self.args.clone();
+        }
+        self.args
+            .iter()
+            .filter_map(|arg| match arg {
+                Argument::Flat(arg) => arg.doc.is_some().then(|| Argument::Flat(arg.clone())),
+                Argument::Pattern {
+                    entries,
+                    ellipsis,
+                    bind,
+                } => {
+                    let entries: Vec<SingleArg> = entries
+                        .iter()
+                        .filter(|arg| arg.doc.is_some())
+                        .cloned()
+                        .collect();
+                    (!entries.is_empty()).then(|| Argument::Pattern {
+                        entries,
+                        ellipsis: *ellipsis,
+                        bind: bind.clone(),
+                    })
+                }
+            })
+            .collect()
+    }
+
     /// Write a single CommonMark entry for a documented Nix function.
     ///
     /// # Arguments
@@ -166,13 +409,20 @@ impl ManualEntry {
     /// - `output`: The output string to append the CommonMark onto.
     pub fn write_section(self, anchor_prefix: &str, output: &mut String) -> String {
         let (ident, title) = self.get_ident_title();
+        // H1 is used for the category, nested entries go one level deeper
+        // than their parent. Commonmark does not support more than H6.
+        let heading = "#".repeat((2 + self.depth).min(6));
         output.push_str(&format!(
-            "## `{}` {{#{}{}}}\n\n",
-            title, anchor_prefix, ident
+            "{} `{}` {{#{}{}}}\n\n",
+            heading, title, anchor_prefix, ident
         ));
 
+        // Doc-comments are rendered verbatim, their type, arguments and
+        // examples are already part of the description.
+        let legacy = self.format == DocFormat::Legacy;
+
         // <subtitle> (type signature)
-        if let Some(t) = &self.fn_type {
+        if let Some(t) = self.fn_type.as_ref().filter(|_| legacy) {
             if t.lines().count() > 1 {
                 output.push_str(&format!("**Type**:\n```\n{}\n```\n\n", t));
             } else {
@@ -187,8 +437,8 @@ impl ManualEntry {
         }
 
         // Function argument names
-        if !self.args.is_empty() {
-            for arg in self.args {
+        if self.renders_args() {
+            for arg in self.rendered_args() {
                 output.push_str(&format!("{}\n", arg.format_argument()));
             }
         }
@@ -197,7 +447,7 @@ impl ManualEntry {
         //
         // TODO: In grhmc's version there are multiple (named)
         // examples, how can this be achieved automatically?
-        if let Some(example) = &self.example {
+        if let Some(example) = self.example.as_ref().filter(|_| legacy) {
             output.push_str(&format!(
                 "::: {{.example #{}example-{}}}\n",
                 anchor_prefix, ident
@@ -207,9 +457,24 @@ impl ManualEntry {
         }
 
         if let Some(loc) = self.location {
-            output.push_str(&String::from(format!("Located at {loc}.\n\n")));
+            output.push_str(&format!("Located at {loc}.\n\n"));
         }
 
         output.to_string()
     }
 }
+
+/// Render a category as CommonMark: the category heading (see
+/// [crate::retrieve_description]) followed by a section per entry.
+pub fn render_commonmark(
+    description: &str,
+    entries: Vec<ManualEntry>,
+    anchor_prefix: &str,
+) -> String {
+    let mut output = format!("{}\n", description);
+
+    for entry in entries {
+        entry.write_section(anchor_prefix, &mut output);
+    }
+    output
+}
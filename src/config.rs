@@ -0,0 +1,173 @@
+//! This module implements the configuration file of `nixdoc build`,
+//! `nixdoc.toml`, which lists the files to document in a single run:
+//!
+//! ```toml
+//! prefix = "lib"
+//! format = "commonmark"
+//!
+//! [[file]]
+//! path = "lib/strings.nix"
+//! category = "strings"
+//! description = "String manipulation functions"
+//! output = "docs/strings.md"
+//! ```
+//!
+//! The settings at the top level apply to every file, unless a file
+//! overrides them. Settings given on the command line take precedence
+//! over both, see [Overrides].
+
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{Format, NixdocError};
+
+/// The contents of a configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Prefix for the categories of all files (e.g. 'lib' or 'utils').
+    pub prefix: Option<String>,
+
+    /// Prefix of the anchors of all files.
+    pub anchor_prefix: Option<String>,
+
+    /// Format of the documentation of all files.
+    pub format: Option<Format>,
+
+    /// Maximum depth of nested attribute sets to document, 1 unless given.
+    pub max_depth: Option<usize>,
+
+    /// JSON file of the locations of the entries of all files, as
+    /// `--locs` reads it.
+    pub locs: Option<PathBuf>,
+
+    /// URL template linking entries to their source, as
+    /// `--source-url-template` takes it.
+    pub source_url_template: Option<String>,
+
+    /// The files to document, in order.
+    #[serde(default, rename = "file")]
+    pub files: Vec<FileConfig>,
+}
+
+/// A file to document, see [Config].
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FileConfig {
+    /// The Nix file to document.
+    pub path: PathBuf,
+
+    /// Name of the function category (e.g. 'strings', 'attrsets').
+    #[serde(default)]
+    pub category: String,
+
+    /// Description of the function category.
+    #[serde(default)]
+    pub description: String,
+
+    pub prefix: Option<String>,
+    pub anchor_prefix: Option<String>,
+    pub format: Option<Format>,
+    pub max_depth: Option<usize>,
+    pub locs: Option<PathBuf>,
+    pub source_url_template: Option<String>,
+
+    /// Where to write the documentation. Without an output, it is printed.
+    pub output: Option<PathBuf>,
+}
+
+/// Settings given on the command line, which take precedence over the
+/// configuration file.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub prefix: Option<String>,
+    pub anchor_prefix: Option<String>,
+    pub format: Option<Format>,
+}
+
+/// A file to document with its settings resolved, see [Config::targets].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub path: PathBuf,
+    pub category: String,
+    pub description: String,
+    pub prefix: String,
+    pub anchor_prefix: String,
+    pub format: Format,
+    pub max_depth: usize,
+    pub locs: Option<PathBuf>,
+    pub source_url_template: Option<String>,
+    pub output: Option<PathBuf>,
+}
+
+impl FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+impl Config {
+    /// Read a configuration file. The paths of files, outputs and
+    /// locations are relative to the directory of the configuration file.
+    pub fn load(path: &Path) -> Result<Config, NixdocError> {
+        let src = fs::read_to_string(path).map_err(|source| NixdocError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut config: Config = src.parse().map_err(|source| NixdocError::Config {
+            path: path.to_path_buf(),
+            source: Box::new(source),
+        })?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        config.locs = config.locs.as_ref().map(|locs| dir.join(locs));
+        for file in &mut config.files {
+            file.path = dir.join(&file.path);
+            file.output = file.output.as_ref().map(|output| dir.join(output));
+            file.locs = file.locs.as_ref().map(|locs| dir.join(locs));
+        }
+        Ok(config)
+    }
+
+    /// Resolve the settings of every file: command line overrides take
+    /// precedence over the settings of the file, which take precedence
+    /// over the top-level settings and the defaults of the command line.
+    pub fn targets(&self, overrides: &Overrides) -> Vec<Target> {
+        self.files
+            .iter()
+            .map(|file| Target {
+                path: file.path.clone(),
+                category: file.category.clone(),
+                description: file.description.clone(),
+                prefix: [&overrides.prefix, &file.prefix, &self.prefix]
+                    .into_iter()
+                    .find_map(Option::clone)
+                    .unwrap_or_else(|| String::from("lib")),
+                anchor_prefix: [
+                    &overrides.anchor_prefix,
+                    &file.anchor_prefix,
+                    &self.anchor_prefix,
+                ]
+                .into_iter()
+                .find_map(Option::clone)
+                .unwrap_or_else(|| String::from("function-library-")),
+                format: [overrides.format, file.format, self.format]
+                    .into_iter()
+                    .flatten()
+                    .next()
+                    .unwrap_or_default(),
+                max_depth: file.max_depth.or(self.max_depth).unwrap_or(1),
+                locs: file.locs.clone().or_else(|| self.locs.clone()),
+                source_url_template: file
+                    .source_url_template
+                    .clone()
+                    .or_else(|| self.source_url_template.clone()),
+                output: file.output.clone(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,265 @@
+//! This module implements the documentation coverage report, which lists
+//! every binding exported by the top-level attribute set of a file, and
+//! whether it and its arguments are documented.
+
+use rnix::ast::{Attr, AttrpathValue, Expr, Inherit};
+use rowan::ast::AstNode;
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::{
+    collect_entry_information,
+    commonmark::{get_title, Argument, DocFormat},
+    get_attr_path,
+    legacy::collect_lambda_args,
+    location::{LineIndex, SourceLocation},
+    resolve::{find_definition, Scope},
+    top_level_attrset, CollectOptions,
+};
+
+/// Documentation coverage of a set of bindings.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Coverage {
+    pub bindings: Vec<BindingCoverage>,
+}
+
+/// Documentation coverage of a single exported binding.
+#[derive(Clone, Debug, Serialize)]
+pub struct BindingCoverage {
+    /// Name of the category the binding belongs to.
+    pub category: String,
+
+    /// Attribute path of the binding relative to the category.
+    pub attr_path: Vec<String>,
+
+    /// Whether the binding, or the definition it inherits, has a doc
+    /// comment.
+    pub documented: bool,
+
+    /// Parameters of the binding's value if it is a lambda, outermost
+    /// first. Parameters of pattern arguments are listed individually.
+    pub args: Vec<ArgCoverage>,
+
+    /// Location of the binding, or of the inherited name.
+    pub location: SourceLocation,
+}
+
+/// Documentation coverage of a lambda parameter.
+#[derive(Clone, Debug, Serialize)]
+pub struct ArgCoverage {
+    pub name: String,
+
+    /// Whether the parameter has a doc comment of its own, or is listed
+    /// in the `# Arguments` section of the binding's doc comment.
+    pub documented: bool,
+}
+
+/// Summary of a [Coverage] for JSON output.
+#[derive(Serialize)]
+struct CoverageSummary<'a> {
+    documented: usize,
+    total: usize,
+    percentage: f64,
+    args_documented: usize,
+    args_total: usize,
+    bindings: &'a [BindingCoverage],
+}
+
+impl Coverage {
+    /// Number of documented bindings.
+    pub fn documented(&self) -> usize {
+        self.bindings.iter().filter(|b| b.documented).count()
+    }
+
+    /// Percentage of documented bindings. A report without bindings is
+    /// fully covered.
+    pub fn percentage(&self) -> f64 {
+        percentage(self.documented(), self.bindings.len())
+    }
+
+    fn args(&self) -> impl Iterator<Item = &ArgCoverage> {
+        self.bindings.iter().flat_map(|b| &b.args)
+    }
+
+    /// Render the report as text, one line per binding followed by a
+    /// summary, e.g.
+    ///
+    /// ```text
+    /// lib/strings.nix:49:3: strings.concatStrings: documented
+    /// lib/strings.nix:58:3: strings.concatMapStrings: undocumented, 0/2 arguments documented (missing: f, list)
+    ///
+    /// 1/2 bindings documented (50.00%)
+    /// 0/2 arguments documented (0.00%)
+    /// ```
+    pub fn render_text(&self) -> String {
+        let mut output = String::new();
+        for binding in &self.bindings {
+            let name = get_title("", &binding.category, &binding.attr_path.join("."));
+            let status = if binding.documented {
+                "documented"
+            } else {
+                "undocumented"
+            };
+            let _ = write!(
+                output,
+                "{}: {}: {}",
+                binding.location.display_position(),
+                name,
+                status
+            );
+
+            let missing: Vec<&str> = binding
+                .args
+                .iter()
+                .filter(|arg| !arg.documented)
+                .map(|arg| arg.name.as_str())
+                .collect();
+            if !binding.args.is_empty() {
+                let _ = write!(
+                    output,
+                    ", {}/{} arguments documented",
+                    binding.args.len() - missing.len(),
+                    binding.args.len()
+                );
+            }
+            if !missing.is_empty() {
+                let _ = write!(output, " (missing: {})", missing.join(", "));
+            }
+            output.push('\n');
+        }
+
+        let args_total = self.args().count();
+        let args_documented = self.args().filter(|arg| arg.documented).count();
+        let _ = write!(
+            output,
+            "\n{}/{} bindings documented ({:.2}%)\n{}/{} arguments documented ({:.2}%)",
+            self.documented(),
+            self.bindings.len(),
+            self.percentage(),
+            args_documented,
+            args_total,
+            percentage(args_documented, args_total)
+        );
+        output
+    }
+
+    /// Render the report and its summary as JSON.
+    pub fn render_json(&self) -> Result<String, serde_json::Error> {
+        let args_total = self.args().count();
+        let args_documented = self.args().filter(|arg| arg.documented).count();
+        serde_json::to_string(&CoverageSummary {
+            documented: self.documented(),
+            total: self.bindings.len(),
+            percentage: self.percentage(),
+            args_documented,
+            args_total,
+            bindings: &self.bindings,
+        })
+    }
+}
+
+fn percentage(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
+
+/// Compute the documentation coverage of the bindings exported by the
+/// top-level attribute set of a parsed Nix file (see
+/// [crate::collect_entries]).
+///
+/// Inherited names are resolved to their definitions where possible.
+/// Names which cannot be resolved statically count as undocumented.
+pub fn collect_coverage(root: &rnix::Root, opts: &CollectOptions) -> Coverage {
+    let Some((set, let_in)) = top_level_attrset(root) else {
+        return Coverage::default();
+    };
+    let lines = opts
+        .lines
+        .clone()
+        .unwrap_or_else(|| Arc::new(LineIndex::new(&root.syntax().to_string())));
+    let mut scope = Scope::new(opts.file.as_deref(), None);
+    if let Some(let_in) = let_in {
+        scope = scope.with_bindings(&let_in);
+    }
+
+    let mut bindings = vec![];
+    for child in set.syntax().children() {
+        if let Some(apv) = AttrpathValue::cast(child.clone()) {
+            bindings.push(binding_coverage(
+                opts,
+                get_attr_path(&apv, &[]),
+                Some(apv.clone()),
+                lines.locate(apv.syntax(), opts.file.clone()),
+            ));
+        } else if let Some(inherit) = Inherit::cast(child) {
+            for attr in inherit.attrs() {
+                let Attr::Ident(ident) = attr else {
+                    continue;
+                };
+                let name = ident.syntax().text().to_string();
+                bindings.push(binding_coverage(
+                    opts,
+                    vec![name.clone()],
+                    find_definition(&set, &scope, &name),
+                    lines.locate(ident.syntax(), opts.file.clone()),
+                ));
+            }
+        }
+    }
+
+    Coverage { bindings }
+}
+
+fn binding_coverage(
+    opts: &CollectOptions,
+    attr_path: Vec<String>,
+    definition: Option<AttrpathValue>,
+    location: SourceLocation,
+) -> BindingCoverage {
+    let item = definition
+        .as_ref()
+        .and_then(|apv| collect_entry_information(apv, &[], 0, &Scope::default()));
+
+    // Names documented in the `# Arguments` section of a doc comment, or
+    // by comments on the lambda parameters of a legacy comment.
+    let documented_args: Vec<&str> = item
+        .iter()
+        .flat_map(|item| flatten_args(&item.args))
+        .filter(|(_, documented)| *documented)
+        .map(|(name, _)| name)
+        .collect();
+
+    let params = match definition.and_then(|apv| apv.value()) {
+        Some(Expr::Lambda(lambda)) => collect_lambda_args(lambda, DocFormat::Legacy),
+        _ => vec![],
+    };
+    let args = flatten_args(&params)
+        .map(|(name, documented)| ArgCoverage {
+            name: name.to_string(),
+            documented: documented || documented_args.contains(&name),
+        })
+        .collect();
+
+    BindingCoverage {
+        category: opts.category.clone(),
+        attr_path,
+        documented: item.is_some(),
+        args,
+        location,
+    }
+}
+
+/// Returns the names of all arguments, including the parameters of
+/// patterns, and whether they have a doc string.
+fn flatten_args(args: &[Argument]) -> impl Iterator<Item = (&str, bool)> {
+    args.iter()
+        .flat_map(|arg| match arg {
+            Argument::Flat(arg) => std::slice::from_ref(arg),
+            Argument::Pattern { entries, .. } => entries.as_slice(),
+        })
+        .map(|arg| (arg.name.as_str(), arg.doc.is_some()))
+}
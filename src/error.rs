@@ -0,0 +1,217 @@
+//! This module implements the error type returned by nixdoc.
+
+use rnix::parser::ParseError;
+use rnix::{TextRange, TextSize};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::location::{line_text, SourceLocation};
+
+/// Errors that can occur while collecting or rendering documentation.
+///
+/// Each class of errors has its own process exit code, see
+/// [NixdocError::exit_code].
+#[derive(Debug)]
+pub enum NixdocError {
+    /// A file could not be read or written.
+    Io { path: PathBuf, source: io::Error },
+
+    /// The Nix source could not be parsed.
+    Parse {
+        /// Where parsing failed.
+        location: SourceLocation,
+
+        /// The source line containing the start of `location`.
+        source_line: String,
+
+        /// The first error reported by the parser.
+        error: ParseError,
+    },
+
+    /// The location information is not a valid JSON object of strings.
+    Locs {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    /// The external link index is not a valid JSON object of strings.
+    LinkIndex {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    /// The configuration file of `nixdoc build` is invalid, see
+    /// [crate::config::Config].
+    Config {
+        path: PathBuf,
+        source: Box<toml::de::Error>,
+    },
+
+    /// Files could not be watched for changes, see `nixdoc watch`.
+    Watch(notify::Error),
+
+    /// Entries could not be serialized to JSON.
+    Json(serde_json::Error),
+
+    /// No entry is named by an attribute path, see [crate::show::find_entry].
+    UnknownEntry {
+        path: String,
+
+        /// Names of similar entries.
+        suggestions: Vec<String>,
+    },
+
+    /// Several entries are named by an attribute path, e.g. a name which
+    /// is defined in more than one category.
+    AmbiguousEntry {
+        path: String,
+        candidates: Vec<String>,
+    },
+}
+
+impl NixdocError {
+    /// Create a [NixdocError::Parse] for a parse error in `src`, which was
+    /// read from `path`.
+    pub fn parse(src: &str, path: Option<PathBuf>, error: ParseError) -> Self {
+        let range = parse_error_range(&error, src);
+        let location = SourceLocation::new(src, path, range);
+        NixdocError::Parse {
+            source_line: line_text(src, location.range.start).to_string(),
+            location,
+            error,
+        }
+    }
+
+    /// The process exit code for this error, following the conventions of
+    /// `sysexits.h`:
+    ///
+    /// - 65 (`EX_DATAERR`): the Nix source could not be parsed.
+    /// - 70 (`EX_SOFTWARE`): entries could not be serialized.
+    /// - 66 (`EX_NOINPUT`): the entry to show does not exist, or is ambiguous.
+    /// - 74 (`EX_IOERR`): a file could not be read, written or watched.
+    /// - 78 (`EX_CONFIG`): the location information, link index or
+    ///   configuration file is invalid.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NixdocError::Parse { .. } => 65,
+            NixdocError::Json(_) => 70,
+            NixdocError::UnknownEntry { .. } | NixdocError::AmbiguousEntry { .. } => 66,
+            NixdocError::Io { .. } | NixdocError::Watch(_) => 74,
+            NixdocError::Locs { .. }
+            | NixdocError::LinkIndex { .. }
+            | NixdocError::Config { .. } => 78,
+        }
+    }
+}
+
+/// Returns the range of source a parse error refers to. Errors about an
+/// unexpected end of file point to the end of the source.
+pub(crate) fn parse_error_range(error: &ParseError, src: &str) -> TextRange {
+    match error {
+        ParseError::Unexpected(range)
+        | ParseError::UnexpectedExtra(range)
+        | ParseError::UnexpectedWanted(_, range, _)
+        | ParseError::UnexpectedDoubleBind(range)
+        | ParseError::DuplicatedArgs(range, _) => *range,
+        _ => TextRange::empty(TextSize::of(src)),
+    }
+}
+
+impl fmt::Display for NixdocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NixdocError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            NixdocError::Parse {
+                location,
+                source_line,
+                error,
+            } => {
+                // Render the error with a snippet of the source, e.g.
+                //
+                // failed to parse input: unexpected TOKEN_SEMICOLON at 10..11, wanted any of [...]
+                //  --> lib/strings.nix:2:9
+                //   |
+                // 2 |   foo = ;
+                //   |         ^
+                let gutter = " ".repeat(location.line.to_string().len());
+                let marker_len = source_line
+                    .chars()
+                    .skip(location.column - 1)
+                    .count()
+                    .min(location.range.len())
+                    .max(1);
+                writeln!(f, "failed to parse input: {}", error)?;
+                writeln!(f, "{}--> {}", gutter, location.display_position())?;
+                writeln!(f, "{} |", gutter)?;
+                writeln!(f, "{} | {}", location.line, source_line)?;
+                write!(
+                    f,
+                    "{} | {}{}",
+                    gutter,
+                    " ".repeat(location.column - 1),
+                    "^".repeat(marker_len)
+                )
+            }
+            NixdocError::Locs { path, source } => write!(
+                f,
+                "could not read location information from {}: {}",
+                path.display(),
+                source
+            ),
+            NixdocError::LinkIndex { path, source } => write!(
+                f,
+                "could not read link index from {}: {}",
+                path.display(),
+                source
+            ),
+            NixdocError::Config { path, source } => write!(
+                f,
+                "could not read configuration from {}: {}",
+                path.display(),
+                source
+            ),
+            NixdocError::Watch(error) => write!(f, "could not watch files: {}", error),
+            NixdocError::Json(error) => {
+                write!(f, "problem converting entries to JSON: {}", error)
+            }
+            NixdocError::UnknownEntry { path, suggestions } => {
+                write!(f, "no entry named `{}`", path)?;
+                match suggestions.as_slice() {
+                    [] => Ok(()),
+                    [suggestion] => write!(f, "\n\ndid you mean `{}`?", suggestion),
+                    suggestions => {
+                        write!(f, "\n\ndid you mean one of these?")?;
+                        for suggestion in suggestions {
+                            write!(f, "\n  {}", suggestion)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            NixdocError::AmbiguousEntry { path, candidates } => {
+                write!(f, "`{}` names several entries:", path)?;
+                for candidate in candidates {
+                    write!(f, "\n  {}", candidate)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for NixdocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NixdocError::Io { source, .. } => Some(source),
+            NixdocError::Parse { error, .. } => Some(error),
+            NixdocError::Locs { source, .. } | NixdocError::LinkIndex { source, .. } => {
+                Some(source)
+            }
+            NixdocError::Config { source, .. } => Some(source.as_ref()),
+            NixdocError::Watch(error) => Some(error),
+            NixdocError::Json(error) => Some(error),
+            NixdocError::UnknownEntry { .. } | NixdocError::AmbiguousEntry { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,459 @@
+//! This module implements extracting the usage examples of doc comments
+//! into tests for `lib.runTests`.
+//!
+//! Examples follow the convention of nixpkgs, where an expression is
+//! followed by the result it evaluates to:
+//!
+//! ```nix
+//! concatStrings [ "foo" "bar" ]
+//! => "foobar"
+//! ```
+//!
+//! A code block may contain several such pairs. Expressions and results
+//! may span multiple lines; a result ends with the first line at which it
+//! is a complete Nix expression.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    commonmark::{DocFormat, ManualEntry},
+    error::parse_error_range,
+    lint::{Diagnostic, Lint},
+    location::{line_column, SourceLocation},
+    sections::{code_blocks, CodeBlock},
+};
+
+/// A usage example which can be run as a test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Example {
+    /// Title of the documented entry, e.g. `lib.strings.concatStrings`.
+    pub title: String,
+
+    /// The expression of the example.
+    pub expr: String,
+
+    /// The result the expression is documented to evaluate to.
+    pub expected: String,
+
+    /// Location of the expression in the doc comment, or of the entry if
+    /// it cannot be found in the source.
+    pub location: SourceLocation,
+}
+
+/// The examples of a set of entries, and the problems with those which do
+/// not follow the convention.
+#[derive(Debug, Default)]
+pub struct Examples {
+    pub examples: Vec<Example>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A pair of an expression and its result, as parsed from a code block.
+struct Pair {
+    /// Index of the first line of the expression in the code block.
+    line: usize,
+    expr: String,
+    expected: String,
+}
+
+/// An example which does not follow the convention.
+struct Invalid {
+    /// Index of the offending line in the code block.
+    line: usize,
+    message: String,
+}
+
+/// Extract the usage examples of entries collected from `src`, which was
+/// read from `file`.
+///
+/// Legacy `Example:` blocks and the code blocks of RFC145 `# Examples`
+/// sections without an info string or with a `nix` info string are
+/// considered. Code blocks which do not follow the convention are
+/// reported as [Lint::InvalidExample].
+pub fn extract_examples(src: &str, file: Option<&Path>, entries: &[ManualEntry]) -> Examples {
+    let mut result = Examples::default();
+
+    for entry in entries {
+        let (_, title) = entry.get_ident_title();
+        let blocks = entry
+            .example_blocks()
+            .into_iter()
+            .filter(|block| block.info.as_deref().is_none_or(is_nix));
+
+        for CodeBlock { code, .. } in blocks {
+            let lines: Vec<&str> = code.lines().collect();
+
+            match split_pairs(&lines) {
+                Ok(pairs) => result
+                    .examples
+                    .extend(pairs.into_iter().map(|pair| Example {
+                        title: title.clone(),
+                        location: locate(src, file, entry, &lines, pair.line, 1),
+                        expr: pair.expr,
+                        expected: pair.expected,
+                    })),
+                Err(invalid) => result.diagnostics.push(Diagnostic {
+                    lint: Lint::InvalidExample,
+                    location: locate(src, file, entry, &lines, invalid.line, 1),
+                    message: format!("example of `{}` {}", title, invalid.message),
+                }),
+            }
+        }
+    }
+
+    result
+}
+
+/// Locate a position in a code block of the doc comment of `entry`,
+/// given by the index of its line and its column (starting at 1) in the
+/// block.
+///
+/// Code blocks are dedented, so the block is found in the source by the
+/// content of its first non-empty line, searching backwards from the
+/// binding of the entry. If it cannot be found, the binding is located
+/// instead. The source is `src`, read from `file`, unless the entry is
+/// inherited from another file, which is read then.
+fn locate(
+    src: &str,
+    file: Option<&Path>,
+    entry: &ManualEntry,
+    lines: &[&str],
+    line: usize,
+    column: usize,
+) -> SourceLocation {
+    let span = entry.span.clone().unwrap_or(SourceLocation {
+        path: None,
+        range: 0..0,
+        line: 1,
+        column: 1,
+    });
+    let Some(first) = lines.iter().position(|line| !line.trim().is_empty()) else {
+        return span;
+    };
+    let src = match &span.path {
+        Some(path) if Some(path.as_path()) != file => match fs::read_to_string(path) {
+            Ok(src) => Cow::Owned(src),
+            Err(_) => return span,
+        },
+        _ => Cow::Borrowed(src),
+    };
+    let src = src.as_ref();
+    let before = src.get(..span.range.start).unwrap_or_default();
+    let line_start = |offset: usize| before[..offset].rfind('\n').map_or(0, |i| i + 1);
+    // Of the lines containing the first line of the block, the last one which
+    // is followed by the rest of the block is preferred.
+    let matches_block = |start: &usize| {
+        src[*start..]
+            .lines()
+            .zip(&lines[first..])
+            .all(|(source, line)| line.trim().is_empty() || source.trim() == line.trim())
+    };
+    let mut candidates = before
+        .rmatch_indices(lines[first].trim())
+        .map(|(offset, _)| line_start(offset));
+    let Some(mut start) = candidates
+        .clone()
+        .find(matches_block)
+        .or_else(|| candidates.next())
+    else {
+        return span;
+    };
+
+    for _ in first..line {
+        let Some(end) = src[start..].find('\n') else {
+            return span;
+        };
+        start += end + 1;
+    }
+
+    // The indentation the block's lines were stripped of in the doc comment.
+    let source_line = src[start..].lines().next().unwrap_or_default();
+    let block_line = lines.get(line).copied().unwrap_or_default();
+    let indentation = |line: &str| line.len() - line.trim_start().len();
+    let shift = indentation(source_line).saturating_sub(indentation(block_line));
+    let offset = block_line
+        .char_indices()
+        .nth(column - 1)
+        .map_or(block_line.len(), |(offset, _)| offset);
+
+    let start = start + shift + offset;
+    let (line, column) = line_column(src, start);
+    SourceLocation {
+        path: span.path,
+        range: start..start,
+        line,
+        column,
+    }
+}
+
+/// Whether the info string of a code block marks it as Nix code, e.g.
+/// `nix` or `nix title="example.nix"`.
+fn is_nix(info: &str) -> bool {
+    info.split_whitespace().next() == Some("nix")
+}
+
+/// Whether `code` parses as a single Nix expression.
+fn is_expression(code: &str) -> bool {
+    !code.trim().is_empty() && rnix::Root::parse(code).errors().is_empty()
+}
+
+/// Create a pair of the lines of an expression and its result, which both
+/// have to parse.
+fn pair(
+    expr: &[&str],
+    expr_line: usize,
+    expected: &[&str],
+    result_line: usize,
+) -> Result<Pair, Invalid> {
+    let (expr, expected) = (expr.join("\n"), expected.join("\n"));
+    if !is_expression(&expr) {
+        return Err(Invalid {
+            line: expr_line,
+            message: format!("has an expression which does not parse: `{}`", expr.trim()),
+        });
+    }
+    if !is_expression(&expected) {
+        return Err(Invalid {
+            line: result_line,
+            message: format!("has a result which does not parse: `{}`", expected.trim()),
+        });
+    }
+    Ok(Pair {
+        line: expr_line,
+        expr: expr.trim_end().to_string(),
+        expected: expected.trim().to_string(),
+    })
+}
+
+/// Split the lines of a code block into pairs of expressions and results.
+fn split_pairs(lines: &[&str]) -> Result<Vec<Pair>, Invalid> {
+    let mut pairs = vec![];
+    let mut expr: Vec<&str> = vec![];
+    let mut expr_line = 0;
+    let mut result: Option<(usize, Vec<&str>)> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+
+        if let Some((_, expected)) = result.as_mut() {
+            let complete = is_expression(&expected.join("\n"));
+            if complete && (line.trim().is_empty() || !trimmed.starts_with("=>")) {
+                let (result_line, expected) = result.take().unwrap();
+                pairs.push(pair(&expr, expr_line, &expected, result_line)?);
+                expr.clear();
+            } else if !line.trim().is_empty() || !expected.is_empty() {
+                expected.push(line);
+                continue;
+            } else {
+                continue;
+            }
+        }
+
+        if let Some(expected) = trimmed.strip_prefix("=>") {
+            if expr.iter().all(|line| line.trim().is_empty()) {
+                return Err(Invalid {
+                    line: index,
+                    message: "has a result without an expression".to_string(),
+                });
+            }
+            let expected = expected.trim_start();
+            result = Some((
+                index,
+                [expected].into_iter().filter(|e| !e.is_empty()).collect(),
+            ));
+        } else if !line.trim().is_empty() || !expr.is_empty() {
+            if expr.is_empty() {
+                expr_line = index;
+            }
+            expr.push(line);
+        }
+    }
+
+    if let Some((result_line, expected)) = result {
+        pairs.push(pair(&expr, expr_line, &expected, result_line)?);
+        expr.clear();
+    }
+    if expr.iter().any(|line| !line.trim().is_empty()) {
+        return Err(Invalid {
+            line: expr_line,
+            message: "has an expression which is not followed by `=> result`".to_string(),
+        });
+    }
+
+    Ok(pairs)
+}
+
+/// Check that the Nix code of the usage examples of entries collected from
+/// `src`, which was read from `file`, parses.
+///
+/// All `nix` code blocks of RFC145 doc comments and legacy `Example:`
+/// blocks are checked, except for the results of `expr => result` pairs,
+/// which often are no valid Nix (e.g. `<LAMBDA>`). Code which parses
+/// neither as an expression nor as the bindings of an attribute set (e.g.
+/// `enable = true;`) is reported as [Lint::ExampleSyntax] at the location
+/// of the error.
+pub fn check_examples(src: &str, file: Option<&Path>, entries: &[ManualEntry]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for entry in entries {
+        let (_, title) = entry.get_ident_title();
+        let blocks: Vec<String> = match entry.format {
+            DocFormat::Legacy => entry.example.clone().into_iter().collect(),
+            DocFormat::Rfc145 => code_blocks(&entry.description.join("\n\n"))
+                .into_iter()
+                .filter(|block| block.info.as_deref().is_some_and(is_nix))
+                .map(|block| block.code)
+                .collect(),
+        };
+
+        for code in &blocks {
+            let lines: Vec<&str> = code.lines().collect();
+            for (first, chunk) in expressions(&lines) {
+                let code = chunk.join("\n");
+                let parsed = rnix::Root::parse(&code);
+                let Some(error) = parsed.errors().first() else {
+                    continue;
+                };
+                if rnix::Root::parse(&format!("{{\n{}\n}}", code))
+                    .errors()
+                    .is_empty()
+                {
+                    continue;
+                }
+
+                let range = parse_error_range(error, &code);
+                let start = usize::from(range.start());
+                let (line, column) = line_column(&code, start);
+                // Unexpected input may span several lines, only its first is shown.
+                let unexpected = code[start..usize::from(range.end())].lines().next();
+                let message = match unexpected.map(str::trim_end) {
+                    None | Some("") => "unexpected end of the code".to_string(),
+                    Some(token) => format!("unexpected `{}`", token),
+                };
+                diagnostics.push(Diagnostic {
+                    lint: Lint::ExampleSyntax,
+                    location: locate(src, file, entry, &lines, first + line - 1, column),
+                    message: format!("example of `{}` does not parse: {}", title, message),
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Split the lines of a code block into its expressions, i.e. the runs of
+/// lines between results of `expr => result` pairs, together with the
+/// index of their first line.
+///
+/// A result starts with `=>` and continues on the lines which are
+/// indented further, or which close a bracket, e.g.
+///
+/// ```nix
+/// => {
+///   x = 1;
+/// }
+/// ```
+fn expressions<'a>(lines: &[&'a str]) -> Vec<(usize, Vec<&'a str>)> {
+    let mut chunks = vec![];
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    let mut result_indentation = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        let indentation = line.len() - trimmed.len();
+
+        if let Some(result_indentation) = result_indentation {
+            let continued = indentation > result_indentation
+                || trimmed.starts_with(['}', ']', ')'])
+                || trimmed.starts_with("''");
+            if !trimmed.is_empty() && continued {
+                continue;
+            }
+        }
+        result_indentation = None;
+
+        if trimmed.starts_with("=>") {
+            result_indentation = Some(indentation);
+            chunks.extend(current.take());
+        } else if !trimmed.is_empty() || current.is_some() {
+            current.get_or_insert((index, vec![])).1.push(line);
+        }
+    }
+
+    chunks.extend(current);
+    chunks
+}
+
+/// Escape a string for use in a double-quoted Nix string.
+fn escape_nix_string(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${")
+}
+
+/// Write an expression as the value of an attribute, on the same line if
+/// it is a single line, or indented on the following lines otherwise.
+fn write_value(output: &mut String, name: &str, value: &str) {
+    if value.contains('\n') {
+        let _ = write!(output, "    {} =", name);
+        for line in value.lines() {
+            output.push('\n');
+            if !line.trim().is_empty() {
+                let _ = write!(output, "      {}", line);
+            }
+        }
+        output.push_str(";\n");
+    } else {
+        let _ = writeln!(output, "    {} = {};", name, value);
+    }
+}
+
+/// Render examples as a Nix file evaluating to the result of
+/// `lib.runTests`, i.e. the list of failed tests.
+///
+/// The file is a function of `lib`, which defaults to the library of
+/// `<nixpkgs>`. Examples are evaluated with `lib` in scope.
+pub fn render_tests(examples: &[Example]) -> String {
+    let mut output = String::from(
+        "# Generated by nixdoc from the usage examples of doc comments.\n\
+         { lib ? import <nixpkgs/lib> }:\n\nwith lib;\n\nrunTests {\n",
+    );
+
+    for (index, example) in examples.iter().enumerate() {
+        // Entries with several examples are numbered, starting at 1.
+        let number = examples[..index]
+            .iter()
+            .filter(|other| other.title == example.title)
+            .count()
+            + 1;
+        let multiple = examples
+            .iter()
+            .filter(|other| other.title == example.title)
+            .count()
+            > 1;
+        let name = match multiple {
+            true => format!("test {} #{}", example.title, number),
+            false => format!("test {}", example.title),
+        };
+
+        if index > 0 {
+            output.push('\n');
+        }
+        let position = match &example.location.path {
+            Some(path) => format!("{}:{}", path.display(), example.location.line),
+            None => format!("line {}", example.location.line),
+        };
+        let _ = writeln!(output, "  # {}", position);
+        let _ = writeln!(output, "  \"{}\" = {{", escape_nix_string(&name));
+        write_value(&mut output, "expr", &example.expr);
+        write_value(&mut output, "expected", &example.expected);
+        output.push_str("  };\n");
+    }
+
+    output.push_str("}\n");
+    output
+}
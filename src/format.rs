@@ -1,3 +1,5 @@
+use rnix::SyntaxKind;
+use rowan::ast::AstNode;
 use textwrap::dedent;
 
 /// Ensure all lines in a multi-line doc-comments have the same indentation.
@@ -16,7 +18,7 @@ use textwrap::dedent;
 ///
 /// The parser turns this into:
 ///
-/// ```
+/// ```text
 /// foo is
 ///   the value:
 ///     10
@@ -28,7 +30,7 @@ use textwrap::dedent;
 ///
 /// What we want instead is:
 ///
-/// ```
+/// ```text
 /// foo is
 /// the value:
 ///   10
@@ -47,6 +49,31 @@ pub fn handle_indentation(raw: &str) -> Option<String> {
     Some(result.trim().to_owned()).filter(|s| !s.is_empty())
 }
 
+/// Put the source of a Nix expression on a single line, e.g. to render the
+/// default value of an argument inline.
+///
+/// Line breaks and indentation between tokens become single spaces, while
+/// strings and other tokens are kept verbatim. Returns `None` if this would
+/// change the expression, i.e. if it contains a string spanning several
+/// lines or a line comment.
+pub fn single_line_expression(src: &str) -> Option<String> {
+    let root = rnix::Root::parse(src).tree();
+    let mut result = String::new();
+    for token in root
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+    {
+        match token.kind() {
+            SyntaxKind::TOKEN_WHITESPACE => result.push(' '),
+            SyntaxKind::TOKEN_COMMENT if token.text().starts_with('#') => return None,
+            _ if token.text().contains('\n') => return None,
+            _ => result.push_str(token.text()),
+        }
+    }
+    Some(result.trim().to_string())
+}
+
 /// Shift down markdown headings
 ///
 /// Performs a line-wise matching to '# Heading '
@@ -65,49 +92,152 @@ pub fn handle_indentation(raw: &str) -> Option<String> {
 pub fn shift_headings(raw: &str, levels: usize) -> String {
     let mut result = String::new();
 
-    let mut curr_fence: Option<(usize, char)> = None;
+    let mut fences = CodeFences::default();
     for raw_line in raw.split_inclusive('\n') {
+        let in_code_block = fences.update(raw_line);
+
+        // Remove up to 0-3 leading whitespaces.
+        // If the line has 4 or more whitespaces it is not a heading according to commonmark spec.
+        let heading_line = &trim_leading_whitespace(raw_line, 3);
+        if !in_code_block && heading_line.starts_with('#') {
+            let heading = handle_heading(heading_line, levels);
+            result.push_str(&heading);
+        } else {
+            result.push_str(raw_line);
+        }
+    }
+    result
+}
+
+/// Tracks the fenced code blocks of a Markdown document, which is fed
+/// to it line by line.
+#[derive(Debug, Default)]
+pub struct CodeFences {
+    curr_fence: Option<(usize, char)>,
+}
+
+impl CodeFences {
+    /// Process the next line of the document.
+    ///
+    /// Returns whether the line is part of a fenced code block, including
+    /// the opening and closing fences.
+    pub fn update(&mut self, line: &str) -> bool {
+        let was_in_code_block = self.curr_fence.is_some();
+
         // Code blocks can only start with backticks or tildes
         // code fences can be indented by 0-3 spaces see commonmark spec.
-        let fence_line = &trim_leading_whitespace(raw_line, 3);
+        let fence_line = &trim_leading_whitespace(line, 3);
         if fence_line.starts_with("```") | fence_line.starts_with("~~~") {
             let fence_info = get_fence(fence_line, true);
-            if curr_fence.is_none() {
+            if self.curr_fence.is_none() {
                 // Start of code block
-                curr_fence = fence_info;
+                self.curr_fence = fence_info;
             } else {
                 // Possible end of code block. Ending fences cannot have info strings
-                match (curr_fence, get_fence(fence_line, false)) {
-                    // End of code block must have the same fence type as the start (~~~ or ```)
-                    // Code blocks must be ended with at least the same number of backticks or tildes as the start fence
-                    (Some((start_count, start_char)), Some((end_count, end_char))) => {
-                        if start_count <= end_count && start_char == end_char {
-                            // End of code block (same fence as start)
-                            curr_fence = None;
-                        }
+                // End of code block must have the same fence type as the start (~~~ or ```)
+                // Code blocks must be ended with at least the same number of backticks or tildes as the start fence
+                if let (Some((start_count, start_char)), Some((end_count, end_char))) =
+                    (self.curr_fence, get_fence(fence_line, false))
+                {
+                    if start_count <= end_count && start_char == end_char {
+                        // End of code block (same fence as start)
+                        self.curr_fence = None;
                     }
-                    _ => {}
-                };
+                }
             }
         }
 
-        // Remove up to 0-3 leading whitespaces.
-        // If the line has 4 or more whitespaces it is not a heading according to commonmark spec.
-        let heading_line = &trim_leading_whitespace(raw_line, 3);
-        if curr_fence.is_none() && heading_line.starts_with('#') {
-            let heading = handle_heading(heading_line, levels);
-            result.push_str(&heading);
-        } else {
-            result.push_str(raw_line);
+        was_in_code_block || self.curr_fence.is_some()
+    }
+
+    /// Whether the lines processed so far end inside a fenced code block.
+    pub fn in_code_block(&self) -> bool {
+        self.curr_fence.is_some()
+    }
+}
+
+/// A part of a line of Markdown, see [inline_code_spans].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Inline<'a> {
+    Text(&'a str),
+
+    /// An inline code span, e.g. `` `foo` ``.
+    Code {
+        /// The whole span, including its backticks.
+        span: &'a str,
+
+        /// The content between the backticks.
+        content: &'a str,
+
+        /// The number of backticks delimiting the span.
+        ticks: usize,
+    },
+}
+
+/// Splits a line of Markdown into text and inline code spans. A span ends
+/// at the next run of as many backticks as it started with, runs without
+/// such an end are text.
+pub fn inline_code_spans(line: &str) -> Vec<Inline<'_>> {
+    let mut parts = vec![];
+    let mut text_start = 0;
+    let mut offset = 0;
+    while let Some(start) = line[offset..].find('`') {
+        let start = offset + start;
+        let ticks = backtick_run(&line[start..]);
+        let content_start = start + ticks;
+        let Some(length) = closing_backticks(&line[content_start..], ticks) else {
+            offset = content_start;
+            continue;
+        };
+        let end = content_start + length + ticks;
+        if text_start < start {
+            parts.push(Inline::Text(&line[text_start..start]));
         }
+        parts.push(Inline::Code {
+            span: &line[start..end],
+            content: &line[content_start..content_start + length],
+            ticks,
+        });
+        text_start = end;
+        offset = end;
     }
-    result
+    if text_start < line.len() {
+        parts.push(Inline::Text(&line[text_start..]));
+    }
+    parts
+}
+
+/// Returns the length of the run of backticks `text` starts with.
+fn backtick_run(text: &str) -> usize {
+    text.len() - text.trim_start_matches('`').len()
+}
+
+/// Returns the offset of the first run of exactly `ticks` backticks.
+fn closing_backticks(text: &str, ticks: usize) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('`') {
+        let start = offset + start;
+        let run = backtick_run(&text[start..]);
+        if run == ticks {
+            return Some(start);
+        }
+        offset = start + run;
+    }
+    None
+}
+
+/// Returns the attributes of a line opening a fenced div, e.g. `{.example}`
+/// of `::: {.example}`, or an empty string for a closing `:::` line.
+pub fn fenced_div(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix(":::")
+        .map(|rest| rest.trim_start_matches(':').trim())
 }
 
 /// Removes leading whitespaces from code fences if present
 /// However maximum of [max] whitespaces are removed.
 /// This is useful for code fences may have leading whitespaces (0-3).
-fn trim_leading_whitespace(input: &str, max: usize) -> String {
+pub fn trim_leading_whitespace(input: &str, max: usize) -> String {
     let mut count = 0;
     input
         .trim_start_matches(|c: char| {
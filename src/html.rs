@@ -0,0 +1,283 @@
+//! This module implements the HTML output: rendering the CommonMark
+//! produced by [render_commonmark] to HTML, and a standalone static site
+//! of one page per category.
+//!
+//! The nixos-render-docs extensions used by the CommonMark output are
+//! supported: heading anchors (`{#anchor}`), definition lists and fenced
+//! divs such as `::: {.example #anchor}`, which become `<div>` elements.
+
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use std::collections::HashMap;
+
+use crate::{
+    commonmark::{render_commonmark, ManualEntry},
+    format::{fenced_div, CodeFences},
+};
+
+/// The stylesheet of a static site, written to `style.css`.
+pub const STYLESHEET: &str = include_str!("site.css");
+
+/// Render a category as HTML, see [render_commonmark].
+pub fn render_html(description: &str, entries: Vec<ManualEntry>, anchor_prefix: &str) -> String {
+    markdown_to_html(
+        &render_commonmark(description, entries, anchor_prefix),
+        |_| None,
+    )
+}
+
+/// A category of a static site, see [render_site].
+pub struct SiteCategory {
+    /// Name of the category, which also names its page (e.g. `strings`
+    /// for `strings.html`).
+    pub name: String,
+
+    /// Title of the category in the index and the sidebar.
+    pub title: String,
+
+    /// The rendered heading and description of the category, see
+    /// [crate::retrieve_description].
+    pub description: String,
+
+    pub entries: Vec<ManualEntry>,
+}
+
+impl SiteCategory {
+    fn page(&self) -> String {
+        format!("{}.html", self.name)
+    }
+}
+
+/// Render a static site of the given categories, returning its files by
+/// their path relative to the root of the site.
+///
+/// The site consists of an `index.html` listing the categories, a page
+/// per category and the stylesheet. Every page has a sidebar listing the
+/// categories and the entries of the current one. Links to the anchors of
+/// entries on other pages (e.g. from `--cross-links`) point to that page.
+pub fn render_site(
+    title: &str,
+    categories: &[SiteCategory],
+    anchor_prefix: &str,
+) -> Vec<(String, String)> {
+    let mut pages = HashMap::new();
+    for category in categories {
+        let page = category.page();
+        pages.insert(
+            format!("sec-functions-library-{}", category.name),
+            page.clone(),
+        );
+        for entry in &category.entries {
+            let (ident, _) = entry.get_ident_title();
+            pages.insert(format!("{}{}", anchor_prefix, ident), page.clone());
+            pages.insert(format!("{}example-{}", anchor_prefix, ident), page.clone());
+        }
+    }
+
+    let mut index = format!("<h1>{}</h1>\n<ul class=\"categories\">\n", escape(title));
+    for category in categories {
+        index.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> ({} {})</li>\n",
+            escape(&category.page()),
+            escape(&category.title),
+            category.entries.len(),
+            if category.entries.len() == 1 {
+                "entry"
+            } else {
+                "entries"
+            }
+        ));
+    }
+    index.push_str("</ul>\n");
+
+    let mut files = vec![
+        ("style.css".to_string(), STYLESHEET.to_string()),
+        (
+            "index.html".to_string(),
+            page(
+                title,
+                title,
+                &sidebar(categories, None, anchor_prefix),
+                &index,
+            ),
+        ),
+    ];
+    for category in categories {
+        let own_page = category.page();
+        let markdown = render_commonmark(
+            &category.description,
+            category.entries.clone(),
+            anchor_prefix,
+        );
+        let content = markdown_to_html(&markdown, |anchor| {
+            pages
+                .get(anchor)
+                .filter(|page| **page != own_page)
+                .map(|page| format!("{}#{}", page, anchor))
+        });
+        files.push((
+            own_page.clone(),
+            page(
+                &format!("{} - {}", category.title, title),
+                title,
+                &sidebar(categories, Some(category), anchor_prefix),
+                &content,
+            ),
+        ));
+    }
+    files
+}
+
+/// Render a complete HTML page.
+fn page(title: &str, site_title: &str, sidebar: &str, content: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{}</title>
+<link rel="stylesheet" href="style.css">
+</head>
+<body>
+<nav class="sidebar">
+<p class="site-title"><a href="index.html">{}</a></p>
+{}</nav>
+<main>
+{}</main>
+</body>
+</html>
+"#,
+        escape(title),
+        escape(site_title),
+        sidebar,
+        content
+    )
+}
+
+/// Render the sidebar listing all categories, and the entries of the
+/// current category.
+fn sidebar(
+    categories: &[SiteCategory],
+    current: Option<&SiteCategory>,
+    anchor_prefix: &str,
+) -> String {
+    let mut sidebar = String::from("<ul>\n");
+    for category in categories {
+        sidebar.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            escape(&category.page()),
+            escape(&category.title)
+        ));
+        if current.is_some_and(|current| std::ptr::eq(current, category)) {
+            sidebar.push_str("\n<ul>\n");
+            for entry in &category.entries {
+                let (ident, title) = entry.get_ident_title();
+                sidebar.push_str(&format!(
+                    "<li><a href=\"#{}{}\" title=\"{}\"><code>{}</code></a></li>\n",
+                    escape(anchor_prefix),
+                    escape(&ident),
+                    escape(&title),
+                    escape(&entry.name())
+                ));
+            }
+            sidebar.push_str("</ul>\n");
+        }
+        sidebar.push_str("</li>\n");
+    }
+    sidebar.push_str("</ul>\n");
+    sidebar
+}
+
+/// Render CommonMark to HTML, rewriting links to anchors (e.g. `#foo`) to
+/// the target returned by `link`, if any.
+fn markdown_to_html(markdown: &str, link: impl Fn(&str) -> Option<String>) -> String {
+    let markdown = fenced_divs(markdown);
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_HEADING_ATTRIBUTES
+        | Options::ENABLE_DEFINITION_LIST;
+    let events = Parser::new_ext(&markdown, options).map(|event| match event {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => {
+            let target = dest_url.strip_prefix('#').and_then(&link);
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url: target.map(CowStr::from).unwrap_or(dest_url),
+                title,
+                id,
+            })
+        }
+        event => event,
+    });
+
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    output
+}
+
+/// Replace fenced divs (e.g. `::: {.example #anchor}` ... `:::`) outside
+/// of code blocks by HTML `<div>` elements, which are separated from their
+/// content by empty lines so it is still parsed as CommonMark.
+fn fenced_divs(markdown: &str) -> String {
+    let mut result = String::new();
+    let mut fences = CodeFences::default();
+    let mut depth = 0;
+    for line in markdown.lines() {
+        match fenced_div(line) {
+            _ if fences.update(line) => result.push_str(line),
+            Some("") if depth > 0 => {
+                depth -= 1;
+                result.push_str("\n</div>\n");
+            }
+            Some(attributes) if !attributes.is_empty() => {
+                depth += 1;
+                result.push_str(&format!("\n{}\n", div_tag(attributes)));
+            }
+            _ => result.push_str(line),
+        }
+        result.push('\n');
+    }
+    result
+}
+
+/// Returns the opening `<div>` tag of a fenced div with the given
+/// attributes, e.g. `{.example #anchor}` or a bare class like `note`.
+fn div_tag(attributes: &str) -> String {
+    let mut classes = vec![];
+    let mut id = None;
+    for attribute in attributes
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split_whitespace()
+    {
+        if let Some(class) = attribute.strip_prefix('.') {
+            classes.push(class);
+        } else if let Some(anchor) = attribute.strip_prefix('#') {
+            id = Some(anchor);
+        } else if !attribute.contains('=') {
+            classes.push(attribute);
+        }
+    }
+
+    let mut tag = String::from("<div");
+    if !classes.is_empty() {
+        tag.push_str(&format!(" class=\"{}\"", escape(&classes.join(" "))));
+    }
+    if let Some(id) = id {
+        tag.push_str(&format!(" id=\"{}\"", escape(id)));
+    }
+    tag.push('>');
+    tag
+}
+
+/// Escape text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,76 @@
+//! This module implements following the imports of a library's entry
+//! file, such as `lib/default.nix` in nixpkgs, to document all of its
+//! categories in a single invocation.
+
+use rnix::ast::{AttrpathValue, Expr, HasEntry};
+use rowan::ast::AstNode;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    format::handle_indentation,
+    legacy::retrieve_legacy_comment,
+    resolve::{import_path, resolve_attrset, Scope},
+    retrieve_doc_comment,
+};
+
+/// A category of a library, defined in a file of its own.
+#[derive(Debug)]
+pub struct ImportedCategory {
+    /// Name of the category, i.e. the attribute the file is bound to.
+    pub category: String,
+
+    /// Description of the category, taken from the first paragraph of the
+    /// doc comment of the binding in the entry file if there is one. It is
+    /// the heading of the category, and thus a single line.
+    pub description: Option<String>,
+
+    /// The imported file.
+    pub file: PathBuf,
+}
+
+/// Collect the categories of a library from its entry file.
+///
+/// Every binding of the entry file's attribute set whose value imports
+/// a relative path, e.g. `strings = import ./strings.nix { inherit lib; };`
+/// or `strings = callLibs ./strings.nix;`, is a category named after the
+/// attribute. Other bindings are ignored.
+pub fn collect_imports(root: &rnix::Root, file: &Path) -> Vec<ImportedCategory> {
+    let Some((set, scope)) = root
+        .expr()
+        .and_then(|expr| resolve_attrset(expr, &Scope::new(Some(file), None)))
+    else {
+        return vec![];
+    };
+
+    set.attrpath_values()
+        .filter_map(|apv| imported_category(&apv, &scope))
+        .collect()
+}
+
+fn imported_category(apv: &AttrpathValue, scope: &Scope) -> Option<ImportedCategory> {
+    let Expr::Apply(apply) = apv.value()? else {
+        return None;
+    };
+    let file = import_path(&apply, scope)?;
+
+    let description = retrieve_doc_comment(apv.syntax(), Some(0))
+        .or(retrieve_legacy_comment(apv.syntax(), false))
+        .and_then(|comment| handle_indentation(&comment))
+        .map(|comment| first_paragraph(&comment));
+
+    Some(ImportedCategory {
+        category: apv.attrpath()?.syntax().text().to_string(),
+        description,
+        file,
+    })
+}
+
+/// Returns the first paragraph of a comment with its line breaks collapsed.
+fn first_paragraph(comment: &str) -> String {
+    comment
+        .lines()
+        .map(str::trim)
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,510 @@
+//! This module implements the versioned JSON output and its schema.
+//!
+//! Version 1 serializes [ManualEntry] as is. Version 2 is decoupled from
+//! the internal representation: every field is always present, argument
+//! kinds are explicitly tagged and attribute paths are arrays.
+
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    commonmark::{get_identifier, Argument, DocFormat, ManualEntry, SingleArg},
+    location::SourceLocation,
+    sections::CodeBlock,
+    signature::{parse_signature, Signature},
+    NixdocError,
+};
+
+/// Version of the JSON output format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JsonVersion {
+    /// The original format, which serializes entries as they are
+    /// represented internally. It remains the default, so that existing
+    /// consumers keep working.
+    #[default]
+    V1,
+
+    /// The documented format described by [json_schema], which is opt-in
+    /// with `--json-version 2`.
+    V2,
+}
+
+impl FromStr for JsonVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(JsonVersion::V1),
+            "2" => Ok(JsonVersion::V2),
+            _ => Err(format!("unsupported JSON version '{}', expected 1 or 2", s)),
+        }
+    }
+}
+
+impl fmt::Display for JsonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonVersion::V1 => write!(f, "1"),
+            JsonVersion::V2 => write!(f, "2"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonFormat<E> {
+    version: u32,
+    entries: Vec<E>,
+}
+
+#[derive(Serialize)]
+struct EntryV2<'a> {
+    prefix: &'a str,
+    category: &'a str,
+    attr_path: &'a [String],
+    identifier: String,
+    format: &'static str,
+    location: Option<&'a str>,
+    span: Option<SpanV2>,
+    #[serde(rename = "type")]
+    fn_type: Option<&'a str>,
+    signature: Option<Signature>,
+    description: String,
+    examples: Vec<CodeBlock>,
+    sections: &'a BTreeMap<String, String>,
+    args: Vec<ArgumentV2<'a>>,
+    inherited_from: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct SpanV2 {
+    file: Option<String>,
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum ArgumentV2<'a> {
+    Flat(&'a SingleArg),
+    Pattern {
+        entries: Vec<PatternEntryV2<'a>>,
+        ellipsis: bool,
+        bind: Option<&'a str>,
+    },
+}
+
+#[derive(Serialize)]
+struct PatternEntryV2<'a> {
+    name: &'a str,
+    doc: Option<&'a str>,
+    default: Option<&'a str>,
+    optional: bool,
+}
+
+impl<'a> PatternEntryV2<'a> {
+    fn new(arg: &'a SingleArg) -> Self {
+        PatternEntryV2 {
+            name: &arg.name,
+            doc: arg.doc.as_deref(),
+            default: arg.default.as_deref(),
+            optional: arg.optional,
+        }
+    }
+}
+
+impl<'a> EntryV2<'a> {
+    fn new(entry: &'a ManualEntry) -> Self {
+        EntryV2 {
+            prefix: &entry.prefix,
+            category: &entry.category,
+            attr_path: &entry.attr_path,
+            identifier: get_identifier(&entry.prefix, &entry.category, &entry.name()),
+            format: match entry.format {
+                DocFormat::Legacy => "legacy",
+                DocFormat::Rfc145 => "rfc145",
+            },
+            location: entry.location.as_deref(),
+            span: entry.span.as_ref().map(SpanV2::new),
+            fn_type: entry.fn_type.as_deref(),
+            signature: entry
+                .fn_type
+                .as_deref()
+                .and_then(|fn_type| parse_signature(fn_type).ok()),
+            description: entry.description.join("\n\n"),
+            examples: entry.example_blocks(),
+            sections: &entry.sections,
+            args: entry
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    Argument::Flat(arg) => ArgumentV2::Flat(arg),
+                    Argument::Pattern {
+                        entries,
+                        ellipsis,
+                        bind,
+                    } => ArgumentV2::Pattern {
+                        entries: entries.iter().map(PatternEntryV2::new).collect(),
+                        ellipsis: *ellipsis,
+                        bind: bind.as_deref(),
+                    },
+                })
+                .collect(),
+            inherited_from: entry.inherited_from.as_deref(),
+        }
+    }
+}
+
+impl SpanV2 {
+    fn new(location: &SourceLocation) -> Self {
+        SpanV2 {
+            file: location
+                .path
+                .as_ref()
+                .map(|path| path.to_string_lossy().replace('\\', "/")),
+            start: location.range.start,
+            end: location.range.end,
+            line: location.line,
+            column: location.column,
+        }
+    }
+}
+
+/// Render entries as JSON in the given format version.
+pub fn render_json(entries: Vec<ManualEntry>, version: JsonVersion) -> Result<String, NixdocError> {
+    match version {
+        JsonVersion::V1 => serde_json::to_string(&JsonFormat {
+            version: 1,
+            entries,
+        }),
+        JsonVersion::V2 => serde_json::to_string(&JsonFormat {
+            version: 2,
+            entries: entries.iter().map(EntryV2::new).collect(),
+        }),
+    }
+    .map_err(NixdocError::Json)
+}
+
+/// Returns the JSON Schema (draft 2020-12) of the given format version,
+/// pretty-printed.
+pub fn json_schema(version: JsonVersion) -> String {
+    let schema = match version {
+        JsonVersion::V1 => schema_v1(),
+        JsonVersion::V2 => schema_v2(),
+    };
+    serde_json::to_string_pretty(&schema).expect("the schema is valid JSON")
+}
+
+fn schema_v1() -> serde_json::Value {
+    let nullable_string = json!({ "type": ["string", "null"] });
+    let single_arg = json!({
+        "type": "object",
+        "required": ["name", "doc"],
+        "properties": {
+            "name": { "type": "string" },
+            "doc": nullable_string,
+        },
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "nixdoc JSON output, version 1",
+        "description": "The default format of --json-output: entries as represented internally by nixdoc 3.x.",
+        "type": "object",
+        "required": ["version", "entries"],
+        "properties": {
+            "version": { "const": 1 },
+            "entries": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["prefix", "category", "location", "name", "fn_type", "description", "example", "args"],
+                    "properties": {
+                        "prefix": { "type": "string" },
+                        "category": { "type": "string" },
+                        "location": nullable_string,
+                        "name": { "type": "string" },
+                        "fn_type": nullable_string,
+                        "description": { "type": "array", "items": { "type": "string" } },
+                        "example": nullable_string,
+                        "args": {
+                            "type": "array",
+                            "items": {
+                                "oneOf": [
+                                    {
+                                        "type": "object",
+                                        "required": ["Flat"],
+                                        "properties": { "Flat": single_arg },
+                                        "additionalProperties": false,
+                                    },
+                                    {
+                                        "type": "object",
+                                        "required": ["Pattern"],
+                                        "properties": { "Pattern": { "type": "array", "items": single_arg } },
+                                        "additionalProperties": false,
+                                    },
+                                ],
+                            },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn schema_v2() -> serde_json::Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "nixdoc JSON output, version 2",
+        "type": "object",
+        "required": ["version", "entries"],
+        "additionalProperties": false,
+        "properties": {
+            "version": { "const": 2 },
+            "entries": {
+                "description": "Documented entries in source order.",
+                "type": "array",
+                "items": { "$ref": "#/$defs/entry" },
+            },
+        },
+        "$defs": {
+            "entry": {
+                "type": "object",
+                "required": [
+                    "prefix", "category", "attr_path", "identifier", "format", "location", "span",
+                    "type", "signature", "description", "examples", "sections", "args", "inherited_from",
+                ],
+                "additionalProperties": false,
+                "properties": {
+                    "prefix": {
+                        "description": "Prefix of the category, e.g. 'lib'. May be empty.",
+                        "type": "string",
+                    },
+                    "category": {
+                        "description": "Name of the category, e.g. 'strings'. May be empty.",
+                        "type": "string",
+                    },
+                    "attr_path": {
+                        "description": "Attribute path of the entry relative to the category, e.g. [\"escape\", \"shell\"].",
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "minItems": 1,
+                    },
+                    "identifier": {
+                        "description": "Identifier used for the anchor of the entry, e.g. 'lib.strings.concatStrings'. Primes are replaced by '-prime'.",
+                        "type": "string",
+                    },
+                    "format": {
+                        "description": "Comment format the entry is documented with. The description of 'rfc145' entries contains all of its sections.",
+                        "enum": ["legacy", "rfc145"],
+                    },
+                    "location": {
+                        "description": "Markdown link to the source of the entry, from --locs or --source-url-template.",
+                        "type": ["string", "null"],
+                    },
+                    "span": {
+                        "description": "Location of the binding in the Nix source.",
+                        "oneOf": [{ "$ref": "#/$defs/span" }, { "type": "null" }],
+                    },
+                    "type": {
+                        "description": "Type signature of the entry. This is not checked in any way.",
+                        "type": ["string", "null"],
+                    },
+                    "signature": {
+                        "description": "The parsed type signature, if it could be parsed.",
+                        "oneOf": [{ "$ref": "#/$defs/signature" }, { "type": "null" }],
+                    },
+                    "description": {
+                        "description": "Markdown description of the entry.",
+                        "type": "string",
+                    },
+                    "examples": {
+                        "description": "Usage examples of the entry.",
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/codeBlock" },
+                    },
+                    "sections": {
+                        "description": "Doc comment sections other than Type, Arguments and Examples, keyed by heading.",
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                    },
+                    "args": {
+                        "description": "Arguments of the function, outermost first.",
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/argument" },
+                    },
+                    "inherited_from": {
+                        "description": "Source of the 'inherit (source)' clause the entry is re-exported with, as an attribute path (e.g. 'lib.strings') or otherwise the expression on a single line.",
+                        "type": ["string", "null"],
+                    },
+                },
+            },
+            "span": {
+                "type": "object",
+                "required": ["file", "start", "end", "line", "column"],
+                "additionalProperties": false,
+                "properties": {
+                    "file": {
+                        "description": "Path of the file, as given to nixdoc.",
+                        "type": ["string", "null"],
+                    },
+                    "start": { "description": "Start byte offset.", "type": "integer", "minimum": 0 },
+                    "end": { "description": "End byte offset (exclusive).", "type": "integer", "minimum": 0 },
+                    "line": { "description": "Line of the start, starting at 1.", "type": "integer", "minimum": 1 },
+                    "column": { "description": "Column of the start in characters, starting at 1.", "type": "integer", "minimum": 1 },
+                },
+            },
+            "argument": {
+                "oneOf": [
+                    {
+                        "description": "A single argument, e.g. 'x' in 'x: x'.",
+                        "type": "object",
+                        "required": ["kind", "name", "doc"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "kind": { "const": "flat" },
+                            "name": { "type": "string" },
+                            "doc": { "type": ["string", "null"] },
+                        },
+                    },
+                    {
+                        "description": "A pattern argument, e.g. 'args@{ a, b ? 1, ... }: a'.",
+                        "type": "object",
+                        "required": ["kind", "entries", "ellipsis", "bind"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "kind": { "const": "pattern" },
+                            "entries": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "required": ["name", "doc", "default", "optional"],
+                                    "additionalProperties": false,
+                                    "properties": {
+                                        "name": { "type": "string" },
+                                        "doc": { "type": ["string", "null"] },
+                                        "default": {
+                                            "description": "Source text of the default value, e.g. '1' in 'b ? 1'.",
+                                            "type": ["string", "null"],
+                                        },
+                                        "optional": {
+                                            "description": "Whether the attribute may be omitted, as it has a default value.",
+                                            "type": "boolean",
+                                        },
+                                    },
+                                },
+                            },
+                            "ellipsis": {
+                                "description": "Whether other attributes are accepted, i.e. the pattern ends in '...'.",
+                                "type": "boolean",
+                            },
+                            "bind": {
+                                "description": "Name the whole attribute set is bound to with '@', e.g. 'args'.",
+                                "type": ["string", "null"],
+                            },
+                        },
+                    },
+                ],
+            },
+            "codeBlock": code_block_schema(),
+            "signature": {
+                "type": "object",
+                "required": ["name", "type"],
+                "additionalProperties": false,
+                "properties": {
+                    "name": {
+                        "description": "The name the signature is given for, i.e. the part before '::'.",
+                        "type": ["string", "null"],
+                    },
+                    "type": { "$ref": "#/$defs/type" },
+                },
+            },
+            "type": type_schema(),
+        },
+    })
+}
+
+fn type_schema() -> serde_json::Value {
+    let variant = |kind: &str, description: &str, properties: serde_json::Value| {
+        let mut required = vec!["kind".to_string()];
+        required.extend(
+            properties
+                .as_object()
+                .into_iter()
+                .flat_map(|p| p.keys().cloned()),
+        );
+        let mut properties = properties;
+        properties["kind"] = json!({ "const": kind });
+        json!({
+            "description": description,
+            "type": "object",
+            "required": required,
+            "additionalProperties": false,
+            "properties": properties,
+        })
+    };
+    let ty = json!({ "$ref": "#/$defs/type" });
+    let types = json!({ "type": "array", "items": ty });
+
+    json!({
+        "oneOf": [
+            variant("var", "A type variable, e.g. 'a'.", json!({ "name": { "type": "string" } })),
+            variant(
+                "named",
+                "A named type and its arguments, e.g. 'string' or 'attrsOf a'.",
+                json!({ "name": { "type": "string" }, "args": types }),
+            ),
+            variant("list", "A list, e.g. '[a]'.", json!({ "element": ty })),
+            variant(
+                "record",
+                "An attribute set. Open attribute sets may contain other attributes.",
+                json!({
+                    "fields": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["name", "type", "optional"],
+                            "additionalProperties": false,
+                            "properties": {
+                                "name": { "type": "string" },
+                                "type": ty,
+                                "optional": { "type": "boolean" },
+                            },
+                        },
+                    },
+                    "open": { "type": "boolean" },
+                }),
+            ),
+            variant(
+                "function",
+                "A function, e.g. 'a -> b'.",
+                json!({ "argument": ty, "result": ty }),
+            ),
+            variant(
+                "union",
+                "A union of types, e.g. 'string | path'.",
+                json!({ "alternatives": types }),
+            ),
+        ],
+    })
+}
+
+fn code_block_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "required": ["info", "code"],
+        "additionalProperties": false,
+        "properties": {
+            "info": {
+                "description": "Info string of the code fence, e.g. 'nix'.",
+                "type": ["string", "null"],
+            },
+            "code": { "type": "string" },
+        },
+    })
+}
@@ -1,41 +1,37 @@
 use rnix::{
     ast::{AstToken, Comment, Expr, Lambda, Param},
-    SyntaxKind, SyntaxNode,
+    SyntaxKind, SyntaxNode, SyntaxToken,
 };
 use rowan::ast::AstNode;
-use std::collections::HashMap;
 
 use crate::{
-    commonmark::{Argument, ManualEntry, SingleArg},
+    commonmark::{get_identifier, Argument, DocFormat, ManualEntry, SingleArg},
     format::handle_indentation,
-    get_identifier, retrieve_doc_comment, DocComment,
+    location::SourceLocation,
+    retrieve_doc_comment, CollectOptions, DocComment,
 };
 
 #[derive(Debug)]
 pub struct LegacyDocItem {
-    pub name: String,
+    pub attr_path: Vec<String>,
     pub comment: DocComment,
     pub args: Vec<Argument>,
+    pub span: Option<SourceLocation>,
 }
 
 impl LegacyDocItem {
-    pub fn into_entry(
-        self,
-        prefix: &str,
-        category: &str,
-        locs: &HashMap<String, String>,
-    ) -> ManualEntry {
-        let ident = get_identifier(
-            &prefix.to_string(),
-            &category.to_string(),
-            &self.name.to_string(),
-        );
+    pub fn into_entry(self, opts: &CollectOptions, depth: usize) -> ManualEntry {
+        let ident = get_identifier(&opts.prefix, &opts.category, &self.attr_path.join("."));
 
         ManualEntry {
-            prefix: prefix.to_string(),
-            category: category.to_string(),
-            location: locs.get(&ident).cloned(),
-            name: self.name,
+            prefix: opts.prefix.clone(),
+            category: opts.category.clone(),
+            location: opts.locs.get(&ident).cloned().or_else(|| {
+                let template = opts.source_url_template.as_ref()?;
+                Some(self.span.as_ref()?.format_link(template))
+            }),
+            attr_path: self.attr_path,
+            depth,
             description: self
                 .comment
                 .doc
@@ -44,13 +40,41 @@ impl LegacyDocItem {
                 .collect(),
             fn_type: self.comment.doc_type,
             example: self.comment.example,
+            examples: self.comment.examples,
+            sections: self.comment.sections,
+            format: self.comment.format,
             args: self.args,
+            arguments_section: self.comment.arguments_section,
+            inherited_from: None,
+            span: self.span,
         }
     }
 }
 
 /// Retrieve documentation comments.
 pub fn retrieve_legacy_comment(node: &SyntaxNode, allow_line_comments: bool) -> Option<String> {
+    let tokens = legacy_comment_tokens(node, allow_line_comments)?;
+    match tokens.as_slice() {
+        [token] if token.text().starts_with("/*") => {
+            Some(Comment::cast(token.clone())?.text().to_string())
+        }
+        _ => Some(
+            tokens
+                .into_iter()
+                .filter_map(Comment::cast)
+                .map(|comment| comment.text().trim().to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    }
+}
+
+/// Returns the comment tokens of the documentation comment of a node in
+/// source order, i.e. a single block comment or adjacent line comments.
+pub(crate) fn legacy_comment_tokens(
+    node: &SyntaxNode,
+    allow_line_comments: bool,
+) -> Option<Vec<SyntaxToken>> {
     // if the current node has a doc comment it'll be immediately preceded by that comment,
     // or there will be a whitespace token and *then* the comment tokens before it. We merge
     // multiple line comments into one large comment if they are on adjacent lines for
@@ -75,72 +99,90 @@ pub fn retrieve_legacy_comment(node: &SyntaxNode, allow_line_comments: bool) ->
     }
 
     if token.text().starts_with("/*") {
-        return Some(Comment::cast(token)?.text().to_string());
+        return Some(vec![token]);
     }
 
     // backtrack to the start of the doc comment, allowing only adjacent line comments.
     // we don't care much about optimization here, doc comments aren't long enough for that.
-    if token.text().starts_with('#') {
-        let mut result = String::new();
-        while let Some(comment) = Comment::cast(token) {
-            if !comment.syntax().text().starts_with('#') {
-                break;
-            }
-            result.insert_str(0, comment.text().trim());
-            let ws = match comment.syntax().prev_token() {
-                Some(t) if t.kind() == SyntaxKind::TOKEN_WHITESPACE => t,
-                _ => break,
-            };
-            // only adjacent lines continue a doc comment, empty lines do not.
-            match ws.text().strip_prefix('\n') {
-                Some(trail) if !trail.contains('\n') => result.insert(0, ' '),
-                _ => break,
-            }
-            token = match ws.prev_token() {
-                Some(c) => c,
-                _ => break,
-            };
+    let mut tokens = vec![];
+    while token.kind() == SyntaxKind::TOKEN_COMMENT && token.text().starts_with('#') {
+        tokens.insert(0, token.clone());
+        let ws = match token.prev_token() {
+            Some(t) if t.kind() == SyntaxKind::TOKEN_WHITESPACE => t,
+            _ => break,
+        };
+        // only adjacent lines continue a doc comment, empty lines do not.
+        match ws.text().strip_prefix('\n') {
+            Some(trail) if !trail.contains('\n') => (),
+            _ => break,
         }
-        return Some(result);
+        token = match ws.prev_token() {
+            Some(c) => c,
+            _ => break,
+        };
     }
-
-    None
+    Some(tokens)
 }
 
 /// Traverse directly chained nix lambdas and collect the identifiers of all lambda arguments
 /// until an unexpected AST node is encountered.
-pub fn collect_lambda_args_legacy(mut lambda: Lambda) -> Vec<Argument> {
+///
+/// The arguments of legacy comments are documented by any comment, those of doc-comments
+/// only by doc-comments on the entries of patterns.
+pub fn collect_lambda_args(mut lambda: Lambda, format: DocFormat) -> Vec<Argument> {
     let mut args = vec![];
 
     loop {
-        match lambda.param().unwrap() {
+        // A lambda without a parameter only occurs in sources with syntax errors.
+        let Some(param) = lambda.param() else {
+            break;
+        };
+        match param {
             // a variable, e.g. `x:` in `id = x: x`
             // Single args are not supported by RFC145, due to ambiguous placement rules.
             Param::IdentParam(id) => {
+                let doc = match format {
+                    DocFormat::Legacy => retrieve_legacy_comment(id.syntax(), true),
+                    DocFormat::Rfc145 => None,
+                };
                 args.push(Argument::Flat(SingleArg {
                     name: id.to_string(),
-                    doc: handle_indentation(
-                        &retrieve_legacy_comment(id.syntax(), true).unwrap_or_default(),
-                    ),
+                    doc: handle_indentation(&doc.unwrap_or_default()),
+                    default: None,
+                    optional: false,
                 }));
             }
             // an ident in a pattern, e.g. `a` in `foo = { a }: a`
             Param::Pattern(pat) => {
                 // collect doc-comments for each lambda formal too
                 // Lambda formals are supported by RFC145
-                let pattern_vec: Vec<_> = pat
+                let entries: Vec<_> = pat
                     .pat_entries()
-                    .map(|entry| SingleArg {
-                        name: entry.ident().unwrap().to_string(),
-                        doc: handle_indentation(
-                            &retrieve_doc_comment(entry.syntax(), Some(1))
-                                .or(retrieve_legacy_comment(entry.syntax(), true))
-                                .unwrap_or_default(),
-                        ),
+                    .filter_map(|entry| {
+                        let doc = retrieve_doc_comment(entry.syntax(), Some(1));
+                        let doc = match format {
+                            DocFormat::Legacy => {
+                                doc.or(retrieve_legacy_comment(entry.syntax(), true))
+                            }
+                            DocFormat::Rfc145 => doc,
+                        };
+                        Some(SingleArg {
+                            name: entry.ident()?.to_string(),
+                            doc: handle_indentation(&doc.unwrap_or_default()),
+                            default: entry.default().map(|expr| expr.syntax().to_string()),
+                            optional: entry.question_token().is_some(),
+                        })
                     })
                     .collect();
 
-                args.push(Argument::Pattern(pattern_vec));
+                args.push(Argument::Pattern {
+                    entries,
+                    ellipsis: pat.ellipsis_token().is_some(),
+                    bind: pat
+                        .pat_bind()
+                        .and_then(|bind| bind.ident())
+                        .map(|ident| ident.to_string()),
+                });
             }
         }
 
@@ -0,0 +1,693 @@
+// Copyright (C) 2018 Vincent Ambo <mail@tazj.in>
+//
+// nixdoc is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! nixdoc generates reference documentation for Nix library functions,
+//! such as the files in `lib/` in the nixpkgs repository.
+//!
+//! This crate exposes the collection of documented entries from Nix
+//! source code and their rendering as CommonMark or JSON. The `nixdoc`
+//! binary is a thin command line interface on top of it.
+//!
+//! ```no_run
+//! let src = std::fs::read_to_string("lib/strings.nix")?;
+//! let options = nixdoc::CollectOptions {
+//!     category: "strings".to_string(),
+//!     ..Default::default()
+//! };
+//! let entries = nixdoc::collect_entries_from_str(&src, &options)?;
+//! let markdown = nixdoc::render_commonmark("", entries, "function-library-");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod anchors;
+pub mod cache;
+mod comment;
+pub mod commonmark;
+pub mod config;
+pub mod coverage;
+mod error;
+pub mod examples;
+mod format;
+pub mod html;
+pub mod imports;
+mod json;
+mod legacy;
+pub mod links;
+pub mod lint;
+pub mod location;
+pub mod man;
+pub mod migrate;
+mod resolve;
+pub mod search;
+mod sections;
+pub mod show;
+pub mod signature;
+#[cfg(test)]
+mod test;
+
+use crate::{format::handle_indentation, legacy::retrieve_legacy_comment};
+
+use self::comment::get_expr_docs;
+use format::{shift_headings, single_line_expression};
+use legacy::{collect_lambda_args, LegacyDocItem};
+use location::LineIndex;
+use resolve::{find_definition, resolve_attrset, Scope};
+use rnix::{
+    ast::{Attr, AttrSet, AttrpathValue, Expr, HasEntry, Inherit, InheritFrom, LetIn},
+    SyntaxKind, SyntaxNode,
+};
+use rowan::{ast::AstNode, WalkEvent};
+use sections::{parse_sections, DocSections};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use std::collections::{BTreeMap, HashMap};
+
+pub use commonmark::{render_commonmark, Argument, DocFormat, ManualEntry, SingleArg};
+pub use error::NixdocError;
+pub use html::render_html;
+pub use json::{json_schema, render_json, JsonVersion};
+pub use man::render_man;
+pub use resolve::dependencies;
+pub use search::render_search_index;
+pub use sections::CodeBlock;
+
+/// Options controlling which entries are collected from a Nix file and
+/// how they are named.
+#[derive(Clone, Debug)]
+pub struct CollectOptions {
+    /// Prefix for the category (e.g. 'lib' or 'utils').
+    pub prefix: String,
+
+    /// Name of the function category (e.g. 'strings', 'attrsets').
+    pub category: String,
+
+    /// Location information of entries, keyed by their identifier
+    /// (e.g. 'lib.strings.concatStrings').
+    pub locs: HashMap<String, String>,
+
+    /// The file the Nix source was read from. Relative imports are
+    /// resolved against it.
+    pub file: Option<PathBuf>,
+
+    /// Maximum depth of nested attribute sets to document.
+    /// A depth of 1 only documents the top-level attribute set.
+    pub max_depth: usize,
+
+    /// Template for links to the source of entries without location
+    /// information in `locs`, e.g. `https://example.com/{path}#L{line}`.
+    /// `{path}`, `{line}` and `{column}` are replaced by the entry's
+    /// location.
+    pub source_url_template: Option<String>,
+
+    /// Whether to locate entries in their source, see [ManualEntry::span].
+    /// Output formats which show neither spans nor links to the source do
+    /// not need them.
+    pub spans: bool,
+
+    /// The line index of the Nix source, to locate entries in it. It is
+    /// computed from the parsed source if not given.
+    pub lines: Option<Arc<LineIndex>>,
+}
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        CollectOptions {
+            prefix: String::from("lib"),
+            category: String::new(),
+            locs: HashMap::new(),
+            file: None,
+            max_depth: 1,
+            source_url_template: None,
+            spans: true,
+            lines: None,
+        }
+    }
+}
+
+/// Format of the rendered documentation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// CommonMark with the extensions of nixos-render-docs.
+    #[default]
+    CommonMark,
+
+    /// HTML, which can be viewed without further processing.
+    Html,
+
+    /// Manual pages in roff, for `man`.
+    Man,
+
+    /// A JSON index for searching the documentation on the client side,
+    /// see [render_search_index].
+    SearchIndex,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commonmark" => Ok(Format::CommonMark),
+            "html" => Ok(Format::Html),
+            "man" => Ok(Format::Man),
+            "search-index" => Ok(Format::SearchIndex),
+            _ => Err(format!(
+                "unsupported format '{}', expected commonmark, html, man or search-index",
+                s
+            )),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Format {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::CommonMark => write!(f, "commonmark"),
+            Format::Html => write!(f, "html"),
+            Format::Man => write!(f, "man"),
+            Format::SearchIndex => write!(f, "search-index"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DocComment {
+    /// Primary documentation string.
+    doc: String,
+
+    /// Optional type annotation for the thing being documented.
+    /// For doc-comments this is parsed from the `# Type` section.
+    doc_type: Option<String>,
+
+    /// Usage example(s) (interpreted as a single code block)
+    /// For doc-comments this is the first code block of the `# Examples` section.
+    example: Option<String>,
+
+    /// All code blocks of the `# Examples` section.
+    /// This is only available for doc-comments.
+    examples: Vec<CodeBlock>,
+
+    /// Sections other than `# Type`, `# Arguments` and `# Examples`.
+    /// This is only available for doc-comments.
+    sections: BTreeMap<String, String>,
+
+    /// Whether the arguments are documented by an `# Arguments` section.
+    /// This is only available for doc-comments.
+    arguments_section: bool,
+
+    format: DocFormat,
+}
+
+#[derive(Debug)]
+struct DocItem {
+    attr_path: Vec<String>,
+    comment: DocComment,
+
+    /// Arguments documented in the `# Arguments` section.
+    args: Vec<Argument>,
+}
+
+enum DocItemOrLegacy {
+    LegacyDocItem(LegacyDocItem),
+    DocItem(DocItem),
+}
+
+/// Returns a rfc145 doc-comment if one is present
+pub fn retrieve_doc_comment(node: &SyntaxNode, shift_headings_by: Option<usize>) -> Option<String> {
+    let doc_comment = get_expr_docs(node);
+
+    doc_comment.map(|doc_comment| {
+        shift_headings(
+            &handle_indentation(&doc_comment).unwrap_or_default(),
+            // H1 to H4 can be used in the doc-comment with the current rendering.
+            // They will be shifted to H3, H6
+            // H1 and H2 are currently used by the outer rendering. (category and function name)
+            shift_headings_by.unwrap_or(2),
+        )
+    })
+}
+
+/// Returns the sections of a node's rfc145 doc-comment, if it has one.
+fn doc_sections(node: &SyntaxNode) -> Option<DocSections> {
+    get_expr_docs(node)
+        .and_then(|doc| handle_indentation(&doc))
+        .map(|doc| parse_sections(&doc))
+}
+
+/// Transforms an AST node into a `DocItem` if it has a leading
+/// documentation comment.
+///
+/// `parent_path` is the attribute path of the enclosing attribute set
+/// and `depth` the number of documented entries above this one, which
+/// determines how far headings in the doc-comment are shifted.
+fn retrieve_doc_item(
+    node: &AttrpathValue,
+    parent_path: &[String],
+    depth: usize,
+) -> Option<DocItemOrLegacy> {
+    let attr_path = get_attr_path(node, parent_path);
+
+    let doc_comment = retrieve_doc_comment(node.syntax(), Some(2 + depth));
+    match doc_comment {
+        Some(comment) => {
+            // Sections are parsed before their headings are shifted.
+            let sections = doc_sections(node.syntax()).unwrap_or_default();
+            Some(DocItemOrLegacy::DocItem(DocItem {
+                attr_path,
+                comment: DocComment {
+                    doc: comment,
+                    doc_type: sections.fn_type,
+                    example: sections.examples.first().map(|block| block.code.clone()),
+                    examples: sections.examples,
+                    sections: sections.other,
+                    arguments_section: !sections.args.is_empty(),
+                    format: DocFormat::Rfc145,
+                },
+                args: sections.args,
+            }))
+        }
+        // Fallback to legacy comment is there is no doc_comment
+        None => {
+            let comment = retrieve_legacy_comment(node.syntax(), false)?;
+            Some(DocItemOrLegacy::LegacyDocItem(LegacyDocItem {
+                attr_path,
+                comment: parse_doc_comment(&comment),
+                args: vec![],
+                span: None,
+            }))
+        }
+    }
+}
+
+/// Returns the full attribute path of a binding, i.e. the path of the
+/// enclosing attribute set followed by the binding's own attrpath.
+///
+/// Dynamic and string attrs are kept verbatim. None of these happen in
+/// nixpkgs lib, and they should probably be rejected entirely.
+fn get_attr_path(node: &AttrpathValue, parent_path: &[String]) -> Vec<String> {
+    let mut attr_path = parent_path.to_vec();
+    if let Some(attrpath) = node.attrpath() {
+        attr_path.extend(
+            attrpath
+                .attrs()
+                .map(|attr| attr.syntax().text().to_string()),
+        );
+    }
+    attr_path
+}
+
+/// Dumb, mutable, hacky doc comment "parser".
+fn parse_doc_comment(raw: &str) -> DocComment {
+    enum ParseState {
+        Doc,
+        Type,
+        Example,
+    }
+
+    let mut state = ParseState::Doc;
+
+    // Split the string into three parts, docs, type and example
+    let mut doc_str = String::new();
+    let mut type_str = String::new();
+    let mut example_str = String::new();
+
+    for line in raw.split_inclusive('\n') {
+        let trimmed_line = line.trim();
+        if let Some(suffix) = trimmed_line.strip_prefix("Type:") {
+            state = ParseState::Type;
+            type_str.push_str(suffix);
+            type_str.push('\n');
+        } else if let Some(suffix) = trimmed_line.strip_prefix("Example:") {
+            state = ParseState::Example;
+            example_str.push_str(suffix);
+            example_str.push('\n');
+        } else {
+            match state {
+                ParseState::Doc => doc_str.push_str(line),
+                ParseState::Type => type_str.push_str(line),
+                ParseState::Example => example_str.push_str(line),
+            }
+        }
+    }
+
+    DocComment {
+        doc: handle_indentation(&doc_str).unwrap_or_default(),
+        doc_type: handle_indentation(&type_str),
+        example: handle_indentation(&example_str),
+        examples: vec![],
+        sections: BTreeMap::new(),
+        arguments_section: false,
+        format: DocFormat::Legacy,
+    }
+}
+
+/// Traverse the arena from a SetEntry and collect, where
+/// possible:
+///
+/// 1. The identifier of the set entry itself.
+/// 2. The attached doc comment on the entry.
+/// 3. The argument names of any curried functions (pattern functions
+///    not yet supported).
+///
+/// `scope` is the scope the entry is defined in, used to locate it.
+fn collect_entry_information(
+    entry: &AttrpathValue,
+    parent_path: &[String],
+    depth: usize,
+    scope: &Scope,
+) -> Option<LegacyDocItem> {
+    let doc_item = retrieve_doc_item(entry, parent_path, depth)?;
+    let span = scope.locate(entry.syntax());
+
+    let lambda_args = |format| match entry.value() {
+        Some(Expr::Lambda(l)) => collect_lambda_args(l, format),
+        _ => vec![],
+    };
+    match doc_item {
+        DocItemOrLegacy::LegacyDocItem(v) => Some(LegacyDocItem {
+            args: lambda_args(DocFormat::Legacy),
+            span,
+            ..v
+        }),
+        // Convert DocItems into legacyItem for markdown rendering.
+        // Arguments which are not documented by an `# Arguments` section
+        // are collected from the lambda.
+        DocItemOrLegacy::DocItem(v) => Some(LegacyDocItem {
+            args: match v.comment.arguments_section {
+                true => v.args,
+                false => lambda_args(DocFormat::Rfc145),
+            },
+            attr_path: v.attr_path,
+            comment: v.comment,
+            span,
+        }),
+    }
+}
+
+/// State shared while walking (possibly nested) attribute sets.
+struct CollectContext<'a> {
+    opts: &'a CollectOptions,
+    scope: &'a HashMap<String, ManualEntry>,
+    bindings: &'a Scope,
+    entries: &'a mut Vec<ManualEntry>,
+}
+
+/// Collect the entries of a single attribute set, descending into nested
+/// attribute sets until `max_depth` is reached.
+///
+/// `level` is the nesting level of `set` (1 for the top-level set) and
+/// `depth` the number of documented entries enclosing it.
+fn collect_attrset_bindings(
+    set: &SyntaxNode,
+    parent_path: &[String],
+    level: usize,
+    depth: usize,
+    ctx: &mut CollectContext,
+) {
+    for child in set.children() {
+        if let Some(apv) = AttrpathValue::cast(child.clone()) {
+            let entry = collect_entry_information(&apv, parent_path, depth, ctx.bindings)
+                .map(|di| di.into_entry(ctx.opts, depth));
+            let documented = entry.is_some();
+            ctx.entries.extend(entry);
+
+            if level < ctx.opts.max_depth {
+                if let Some(Expr::AttrSet(nested)) = apv.value() {
+                    collect_attrset_bindings(
+                        nested.syntax(),
+                        &get_attr_path(&apv, parent_path),
+                        level + 1,
+                        depth + usize::from(documented),
+                        ctx,
+                    );
+                }
+            }
+        } else if let Some(inh) = Inherit::cast(child) {
+            if let Some(from) = inh.from() {
+                collect_inherit_from(&inh, &from, parent_path, depth, ctx);
+                continue;
+            }
+            ctx.entries.extend(inh.attrs().filter_map(|a| match a {
+                Attr::Ident(i) => ctx.scope.get(&i.syntax().text().to_string()).cloned().map(
+                    |entry| ManualEntry {
+                        attr_path: [parent_path, &entry.attr_path].concat(),
+                        depth,
+                        ..entry
+                    },
+                ),
+                // ignore non-ident keys. these aren't useful as lib
+                // functions in general anyway.
+                _ => None,
+            }));
+        }
+    }
+}
+
+/// Collect the re-exported entries of `inherit (source) names;` using the
+/// doc comments of their original definitions.
+///
+/// Sources which cannot be resolved statically (see [resolve]) are
+/// skipped, as are names without a documented definition.
+fn collect_inherit_from(
+    inh: &Inherit,
+    from: &InheritFrom,
+    parent_path: &[String],
+    depth: usize,
+    ctx: &mut CollectContext,
+) {
+    let Some((source, scope)) = from.expr().and_then(|e| resolve_attrset(e, ctx.bindings)) else {
+        return;
+    };
+    let inherited_from = from.expr().map(|e| inherit_source(&e));
+
+    for attr in inh.attrs() {
+        let Attr::Ident(ident) = attr else {
+            continue;
+        };
+        let entry = find_definition(&source, &scope, &ident.syntax().text().to_string())
+            .and_then(|apv| collect_entry_information(&apv, parent_path, depth, &scope))
+            .map(|di| ManualEntry {
+                inherited_from: inherited_from.clone(),
+                ..di.into_entry(ctx.opts, depth)
+            });
+        ctx.entries.extend(entry);
+    }
+}
+
+/// Describe the source of `inherit (source) names;` on a single line: the
+/// attribute path of selections and identifiers, e.g. `lib.strings`, and
+/// the whitespace-normalized source of any other expression.
+fn inherit_source(expr: &Expr) -> String {
+    fn attr_path(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Ident(ident) => Some(ident.to_string()),
+            Expr::Paren(paren) => attr_path(&paren.expr()?),
+            Expr::Select(select) if select.default_expr().is_none() => {
+                let mut path = vec![attr_path(&select.expr()?)?];
+                for attr in select.attrpath()?.attrs() {
+                    match attr {
+                        Attr::Ident(ident) => path.push(ident.to_string()),
+                        _ => return None,
+                    }
+                }
+                Some(path.join("."))
+            }
+            _ => None,
+        }
+    }
+
+    let src = expr.syntax().text().to_string();
+    attr_path(expr)
+        .or_else(|| single_line_expression(&src))
+        .unwrap_or_else(|| src.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Find the top-level attribute set of a parsed Nix file, i.e. the first
+/// attribute set outside of lambda patterns, or the first one in the body
+/// of a top-level `let ... in` expression, which is returned as well.
+fn top_level_attrset(root: &rnix::Root) -> Option<(AttrSet, Option<LetIn>)> {
+    let mut preorder = root.syntax().preorder();
+    while let Some(ev) = preorder.next() {
+        match ev {
+            // Skip patterns. See test/patterns.nix for the reason why.
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_PATTERN => {
+                preorder.skip_subtree();
+            }
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_LET_IN => {
+                let let_in = LetIn::cast(n)?;
+                // A `let` without a body only occurs in sources with syntax errors.
+                let set = let_in
+                    .body()?
+                    .syntax()
+                    .descendants()
+                    .find_map(AttrSet::cast)?;
+                return Some((set, Some(let_in)));
+            }
+            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
+                return Some((AttrSet::cast(n)?, None));
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Collect the documented entries of a parsed Nix file.
+///
+/// Entries are taken from the top-level attribute set, or the body of a
+/// top-level `let ... in` expression. Bindings of the `let` are only
+/// documented if they are inherited into the body.
+pub fn collect_entries(root: rnix::Root, opts: &CollectOptions) -> Vec<ManualEntry> {
+    let Some((set, let_in)) = top_level_attrset(&root) else {
+        return vec![];
+    };
+
+    // we will look into the top-level let and its body for function docs.
+    // we only need a single level of scope for this.
+    // since only the body can export a function we don't need to implement
+    // mutually recursive resolution.
+    let lines = opts.spans.then(|| {
+        opts.lines
+            .clone()
+            .unwrap_or_else(|| Arc::new(LineIndex::new(&root.syntax().to_string())))
+    });
+    let bindings = Scope::new(opts.file.as_deref(), lines);
+    let (scope, bindings) = match let_in {
+        Some(let_in) => (
+            let_in
+                .attrpath_values()
+                .filter_map(|apv| collect_entry_information(&apv, &[], 0, &bindings))
+                .map(|di| (di.attr_path.join("."), di.into_entry(opts, 0)))
+                .collect(),
+            bindings.with_bindings(&let_in),
+        ),
+        None => (HashMap::new(), bindings),
+    };
+
+    let mut entries = vec![];
+    collect_attrset_bindings(
+        set.syntax(),
+        &[],
+        1,
+        0,
+        &mut CollectContext {
+            opts,
+            scope: &scope,
+            bindings: &bindings,
+            entries: &mut entries,
+        },
+    );
+    entries
+}
+
+/// The bindings of a parsed Nix file which [collect_entries] documents:
+/// those of a top-level `let ... in` expression, and those of the
+/// top-level attribute set and of its nested attribute sets until
+/// `max_depth` is reached.
+pub(crate) fn collected_bindings(root: &rnix::Root, max_depth: usize) -> Vec<AttrpathValue> {
+    fn walk(set: &SyntaxNode, level: usize, max_depth: usize, bindings: &mut Vec<AttrpathValue>) {
+        for apv in set.children().filter_map(AttrpathValue::cast) {
+            if level < max_depth {
+                if let Some(Expr::AttrSet(nested)) = apv.value() {
+                    walk(nested.syntax(), level + 1, max_depth, bindings);
+                }
+            }
+            bindings.push(apv);
+        }
+    }
+
+    let Some((set, let_in)) = top_level_attrset(root) else {
+        return vec![];
+    };
+    let mut bindings: Vec<AttrpathValue> = let_in
+        .iter()
+        .flat_map(|let_in| let_in.attrpath_values())
+        .collect();
+    walk(set.syntax(), 1, max_depth, &mut bindings);
+    bindings
+}
+
+/// Parse Nix source code and collect its documented entries.
+pub fn collect_entries_from_str(
+    src: &str,
+    opts: &CollectOptions,
+) -> Result<Vec<ManualEntry>, NixdocError> {
+    Ok(collect_entries(parse(src)?, opts))
+}
+
+/// Read and parse a Nix file.
+pub fn parse_file(file: &Path) -> Result<rnix::Root, NixdocError> {
+    let src = fs::read_to_string(file).map_err(|source| NixdocError::Io {
+        path: file.to_path_buf(),
+        source,
+    })?;
+    rnix::Root::parse(&src)
+        .ok()
+        .map_err(|error| NixdocError::parse(&src, Some(file.to_path_buf()), error))
+}
+
+/// Parse Nix source code, failing on the first syntax error.
+pub fn parse(src: &str) -> Result<rnix::Root, NixdocError> {
+    rnix::Root::parse(src)
+        .ok()
+        .map_err(|error| NixdocError::parse(src, None, error))
+}
+
+/// Read location information of entries from a JSON file mapping
+/// identifiers (e.g. 'lib.strings.concatStrings') to their location.
+pub fn read_locs(file: &Path) -> Result<HashMap<String, String>, NixdocError> {
+    let json = fs::read_to_string(file).map_err(|source| NixdocError::Io {
+        path: file.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&json).map_err(|source| NixdocError::Locs {
+        path: file.to_path_buf(),
+        source,
+    })
+}
+
+/// Render the heading of a category, followed by the doc comment at the
+/// top of the file.
+///
+/// Nothing is rendered if both `description` and `category` are empty.
+pub fn retrieve_description(nix: &rnix::Root, description: &str, category: &str) -> String {
+    if description.is_empty() && category.is_empty() {
+        return String::new();
+    }
+    format!(
+        "# {} {{#sec-functions-library-{}}}\n{}\n",
+        description,
+        category,
+        &nix.syntax()
+            .first_child()
+            .and_then(|node| retrieve_doc_comment(&node, Some(1))
+                .or(retrieve_legacy_comment(&node, false)))
+            .and_then(|doc_item| handle_indentation(&doc_item))
+            .unwrap_or_default()
+    )
+}
@@ -0,0 +1,161 @@
+//! This module implements cross-linking of the documented entries, i.e.
+//! rewriting inline code spans such as `` `concatStrings` `` in their
+//! descriptions into links to the entries they name.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::{
+    commonmark::ManualEntry,
+    format::{inline_code_spans, trim_leading_whitespace, CodeFences, Inline},
+    NixdocError,
+};
+
+/// A link target of an index, together with the category of the entry it
+/// belongs to. External targets have no category.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Target {
+    category: Option<String>,
+    link: String,
+}
+
+/// The names which can be linked to and their link targets.
+///
+/// Entries can be referenced by any of their names, e.g.
+/// `lib.strings.concatStrings` or `concatStrings`. Ambiguous names are resolved to the entry in the same category as the
+/// reference, or not at all.
+#[derive(Clone, Debug, Default)]
+pub struct LinkIndex {
+    targets: HashMap<String, Vec<Target>>,
+}
+
+impl LinkIndex {
+    /// Create an index of entries, which are linked to by their anchors
+    /// (e.g. `#function-library-lib.strings.concatStrings`).
+    pub fn new<'a>(
+        entries: impl IntoIterator<Item = &'a ManualEntry>,
+        anchor_prefix: &str,
+    ) -> Self {
+        let mut index = LinkIndex::default();
+        for entry in entries {
+            let target = Target {
+                category: Some(entry.category.clone()),
+                link: format!("#{}{}", anchor_prefix, entry.get_ident_title().0),
+            };
+            for name in entry.names() {
+                index.insert(name, target.clone());
+            }
+        }
+        index
+    }
+
+    /// Add external link targets by their full name, e.g.
+    /// `lib.attrsets.mapAttrs` to a URL of its documentation.
+    pub fn extend_external(&mut self, targets: HashMap<String, String>) {
+        for (name, link) in targets {
+            self.insert(
+                name,
+                Target {
+                    category: None,
+                    link,
+                },
+            );
+        }
+    }
+
+    fn insert(&mut self, name: String, target: Target) {
+        let targets = self.targets.entry(name).or_default();
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    /// Returns the link target of `name`, referenced from an entry in
+    /// `category`.
+    fn resolve(&self, name: &str, category: &str) -> Option<&str> {
+        match self.targets.get(name)?.as_slice() {
+            [target] => Some(&target.link),
+            targets => {
+                let mut same_category = targets
+                    .iter()
+                    .filter(|target| target.category.as_deref() == Some(category));
+                match (same_category.next(), same_category.next()) {
+                    (Some(target), None) => Some(&target.link),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Read external link targets from a JSON file mapping names (e.g.
+/// 'lib.attrsets.mapAttrs') to links, see [LinkIndex::extend_external].
+pub fn read_link_index(file: &Path) -> Result<HashMap<String, String>, NixdocError> {
+    let json = fs::read_to_string(file).map_err(|source| NixdocError::Io {
+        path: file.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&json).map_err(|source| NixdocError::LinkIndex {
+        path: file.to_path_buf(),
+        source,
+    })
+}
+
+/// Rewrite inline code spans in the descriptions of entries which name
+/// another entry of the index into links to that entry.
+///
+/// Code spans in fenced code blocks or headings, code spans which already
+/// are the text of a link and references of an entry to itself are left
+/// as they are.
+pub fn link_references(entries: &mut [ManualEntry], index: &LinkIndex, anchor_prefix: &str) {
+    for entry in entries {
+        let own_link = format!("#{}{}", anchor_prefix, entry.get_ident_title().0);
+        let resolve = |name: &str| {
+            index
+                .resolve(name, &entry.category)
+                .filter(|link| *link != own_link)
+                .map(str::to_string)
+        };
+
+        // Paragraphs are joined, as fenced code blocks may contain empty lines.
+        let description = entry.description.join("\n\n");
+        let mut linked = String::new();
+        let mut fences = CodeFences::default();
+        for line in description.split_inclusive('\n') {
+            if fences.update(line) || trim_leading_whitespace(line, 3).starts_with('#') {
+                linked.push_str(line);
+            } else {
+                linked.push_str(&link_code_spans(line, resolve));
+            }
+        }
+        entry.description = linked.split("\n\n").map(str::to_string).collect();
+    }
+}
+
+/// Rewrite the code spans of a single line, e.g. `` `foo` `` into
+/// `` [`foo`](#anchor) ``, if `resolve` returns a link target for them.
+fn link_code_spans(line: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    for part in inline_code_spans(line) {
+        match part {
+            Inline::Text(text) => result.push_str(text),
+            Inline::Code {
+                span,
+                content,
+                ticks,
+            } => {
+                // Code spans delimited by multiple backticks contain
+                // backticks, and thus are no identifiers.
+                let link = (ticks == 1 && !result.ends_with('['))
+                    .then(|| resolve(content.trim()))
+                    .flatten();
+                match link {
+                    Some(link) => result.push_str(&format!("[{}]({})", span, link)),
+                    None => result.push_str(span),
+                }
+            }
+        }
+    }
+    result
+}
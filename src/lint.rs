@@ -0,0 +1,349 @@
+//! This module implements linting of doc comments against the code they
+//! document, such as arguments which are documented but do not exist.
+
+use rnix::ast::{AttrpathValue, Expr, Lambda, Param};
+use rnix::SyntaxNode;
+use rowan::ast::AstNode;
+use std::fmt;
+use std::path::Path;
+
+use crate::{
+    collect_entry_information, collected_bindings,
+    commonmark::Argument,
+    doc_sections,
+    format::handle_indentation,
+    legacy::retrieve_legacy_comment,
+    location::{LineIndex, SourceLocation},
+    resolve::Scope,
+    retrieve_doc_comment,
+    signature::parse_signature,
+};
+
+/// The kinds of problems reported by [lint].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// An argument is documented, but not a parameter of the lambda.
+    UnknownArgument,
+
+    /// A parameter is not documented, although other arguments are.
+    UndocumentedArgument,
+
+    /// Arguments of a curried function are documented in a different
+    /// order than the parameters.
+    ArgumentOrder,
+
+    /// A type signature could not be parsed.
+    InvalidSignature,
+
+    /// A type signature is given for a different name than the binding's.
+    SignatureName,
+
+    /// A lambda takes more arguments than its type signature allows.
+    SignatureArity,
+
+    /// Two anchors of the generated documentation are the same.
+    DuplicateAnchor,
+
+    /// A link to an anchor which is not defined.
+    BrokenLink,
+
+    /// A usage example does not follow the `expr => result` convention,
+    /// see [crate::examples].
+    InvalidExample,
+
+    /// The Nix code of a usage example does not parse.
+    ExampleSyntax,
+}
+
+impl Lint {
+    /// Name of the lint, e.g. `unknown-argument`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnknownArgument => "unknown-argument",
+            Lint::UndocumentedArgument => "undocumented-argument",
+            Lint::ArgumentOrder => "argument-order",
+            Lint::InvalidSignature => "invalid-signature",
+            Lint::SignatureName => "signature-name",
+            Lint::SignatureArity => "signature-arity",
+            Lint::DuplicateAnchor => "duplicate-anchor",
+            Lint::BrokenLink => "broken-link",
+            Lint::InvalidExample => "invalid-example",
+            Lint::ExampleSyntax => "example-syntax",
+        }
+    }
+
+    /// Whether problems of this kind are errors rather than warnings.
+    /// Errors produce broken documentation and fail regardless of
+    /// `--deny-warnings`.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Lint::DuplicateAnchor)
+    }
+}
+
+/// A problem found by [lint].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub location: SourceLocation,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    /// Formats the diagnostic as `path:line:column: warning: message [lint]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {} [{}]",
+            self.location.display_position(),
+            if self.lint.is_error() {
+                "error"
+            } else {
+                "warning"
+            },
+            self.message,
+            self.lint.name()
+        )
+    }
+}
+
+/// A parameter of a lambda, possibly of a pattern.
+struct LambdaParam {
+    name: String,
+    node: SyntaxNode,
+
+    /// Whether the parameter has a comment of its own.
+    documented: bool,
+
+    /// Whether the parameter is a curried argument, rather than an entry
+    /// of a pattern.
+    curried: bool,
+}
+
+/// Lint the doc comments of the bindings which [crate::collect_entries]
+/// documents in a parsed Nix file, which was read from `file`.
+///
+/// The arguments documented in the `# Arguments` section of a doc
+/// comment, or with comments on the parameters themselves, are checked
+/// against the parameters of the bound lambda. Bindings which do not
+/// document any argument are not linted, see [crate::coverage] instead.
+///
+/// `max_depth` is the depth of nested attribute sets whose bindings are
+/// linted, as in [crate::CollectOptions]. `lines` is the line index of the
+/// source of `root`, which locates the diagnostics.
+pub fn lint(
+    root: &rnix::Root,
+    file: Option<&Path>,
+    max_depth: usize,
+    lines: &LineIndex,
+) -> Vec<Diagnostic> {
+    let locator = Locator { file, lines };
+    collected_bindings(root, max_depth)
+        .iter()
+        .flat_map(|apv| {
+            let mut diagnostics = lint_arguments(apv, &locator);
+            diagnostics.extend(lint_signature(apv, &locator));
+            diagnostics
+        })
+        .collect()
+}
+
+/// Locates the nodes of the linted file. Nodes are only located once a
+/// diagnostic is reported for them.
+struct Locator<'a> {
+    file: Option<&'a Path>,
+    lines: &'a LineIndex,
+}
+
+impl Locator<'_> {
+    fn locate(&self, node: &SyntaxNode) -> SourceLocation {
+        self.lines.locate(node, self.file.map(Path::to_path_buf))
+    }
+}
+
+/// Returns the attribute path of a binding as written, e.g. `escape.shell`.
+fn binding_name(apv: &AttrpathValue) -> String {
+    apv.attrpath()
+        .map(|attrpath| attrpath.syntax().text().to_string())
+        .unwrap_or_default()
+}
+
+/// Check that the type signature of a binding parses, is given for the
+/// binding's name and allows for as many arguments as its lambda takes.
+fn lint_signature(apv: &AttrpathValue, locator: &Locator) -> Vec<Diagnostic> {
+    let Some(fn_type) = collect_entry_information(apv, &[], 0, &Scope::default())
+        .and_then(|item| item.comment.doc_type)
+    else {
+        return vec![];
+    };
+    let name = binding_name(apv);
+    let location = || locator.locate(apv.syntax());
+
+    let signature = match parse_signature(&fn_type) {
+        Ok(signature) => signature,
+        Err(error) => {
+            return vec![Diagnostic {
+                lint: Lint::InvalidSignature,
+                location: location(),
+                message: format!("invalid type signature of `{}`: {}", name, error.message),
+            }]
+        }
+    };
+
+    let mut diagnostics = vec![];
+    if let Some(signature_name) = &signature.name {
+        // Signatures may be given for the qualified name, e.g.
+        // `lib.strings.concatStrings`, and nested bindings may be
+        // documented by their own name only.
+        let matches = signature_name == &name
+            || signature_name.ends_with(&format!(".{}", name))
+            || name.ends_with(&format!(".{}", signature_name));
+        if !matches {
+            diagnostics.push(Diagnostic {
+                lint: Lint::SignatureName,
+                location: location(),
+                message: format!(
+                    "type signature of `{}` is given for `{}`",
+                    name, signature_name
+                ),
+            });
+        }
+    }
+
+    if let Some(Expr::Lambda(lambda)) = apv.value() {
+        let params = curried_arity(lambda);
+        let arity = signature.ty.arity();
+        if params > arity {
+            diagnostics.push(Diagnostic {
+                lint: Lint::SignatureArity,
+                location: location(),
+                message: format!(
+                    "`{}` takes {} arguments, but its type signature allows for {}",
+                    name, params, arity
+                ),
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// The number of directly chained lambdas, i.e. the number of arguments
+/// a function takes before evaluating its body.
+fn curried_arity(lambda: Lambda) -> usize {
+    match lambda.body() {
+        Some(Expr::Lambda(inner)) => 1 + curried_arity(inner),
+        _ => 1,
+    }
+}
+
+fn lint_arguments(apv: &AttrpathValue, locator: &Locator) -> Vec<Diagnostic> {
+    let Some(Expr::Lambda(lambda)) = apv.value() else {
+        return vec![];
+    };
+    let name = binding_name(apv);
+    let location = || locator.locate(apv.syntax());
+    let params = lambda_params(lambda);
+
+    let documented: Vec<String> = doc_sections(apv.syntax())
+        .map(|sections| sections.args)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|arg| match arg {
+            Argument::Flat(arg) => vec![arg.name],
+            Argument::Pattern { entries, .. } => entries.into_iter().map(|arg| arg.name).collect(),
+        })
+        .collect();
+    if documented.is_empty() && !params.iter().any(|param| param.documented) {
+        return vec![];
+    }
+
+    let mut diagnostics = vec![];
+    for arg in &documented {
+        if !params.iter().any(|param| &param.name == arg) {
+            diagnostics.push(Diagnostic {
+                lint: Lint::UnknownArgument,
+                location: location(),
+                message: format!(
+                    "`{}` documents argument `{}`, which is not a parameter",
+                    name, arg
+                ),
+            });
+        }
+    }
+    for param in &params {
+        if !param.documented && !documented.contains(&param.name) {
+            diagnostics.push(Diagnostic {
+                lint: Lint::UndocumentedArgument,
+                location: locator.locate(&param.node),
+                message: format!("parameter `{}` of `{}` is not documented", param.name, name),
+            });
+        }
+    }
+
+    // Compare the order of curried arguments documented in the arguments
+    // section, ignoring those which are only documented in one place.
+    let curried: Vec<&str> = params
+        .iter()
+        .filter(|param| param.curried && documented.contains(&param.name))
+        .map(|param| param.name.as_str())
+        .collect();
+    let documented_order: Vec<&str> = documented
+        .iter()
+        .map(String::as_str)
+        .filter(|arg| curried.contains(arg))
+        .collect();
+    if curried != documented_order {
+        diagnostics.push(Diagnostic {
+            lint: Lint::ArgumentOrder,
+            location: location(),
+            message: format!(
+                "arguments of `{}` are documented in the order {}, but its parameters are {}",
+                name,
+                documented_order.join(", "),
+                curried.join(", ")
+            ),
+        });
+    }
+
+    diagnostics
+}
+
+/// Collect the parameters of directly chained lambdas, like
+/// [crate::legacy::collect_lambda_args].
+fn lambda_params(mut lambda: Lambda) -> Vec<LambdaParam> {
+    let mut params = vec![];
+
+    loop {
+        match lambda.param() {
+            Some(Param::IdentParam(id)) => params.push(LambdaParam {
+                name: id.to_string(),
+                node: id.syntax().clone(),
+                documented: retrieve_legacy_comment(id.syntax(), true)
+                    .and_then(|doc| handle_indentation(&doc))
+                    .is_some(),
+                curried: true,
+            }),
+            Some(Param::Pattern(pat)) => {
+                params.extend(pat.pat_entries().filter_map(|entry| {
+                    Some(LambdaParam {
+                        name: entry.ident()?.to_string(),
+                        node: entry.syntax().clone(),
+                        documented: retrieve_doc_comment(entry.syntax(), Some(1))
+                            .or(retrieve_legacy_comment(entry.syntax(), true))
+                            .and_then(|doc| handle_indentation(&doc))
+                            .is_some(),
+                        curried: false,
+                    })
+                }));
+            }
+            None => break,
+        }
+
+        match lambda.body() {
+            Some(Expr::Lambda(inner)) => lambda = inner,
+            _ => break,
+        }
+    }
+
+    params
+}
@@ -0,0 +1,136 @@
+//! This module implements mapping byte offsets in Nix source code to
+//! human-readable locations.
+
+use rnix::{SyntaxNode, TextRange};
+use serde::Serialize;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// A location in Nix source code.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    /// The file the source was read from, if any.
+    pub path: Option<PathBuf>,
+
+    /// Byte range of the located syntax.
+    pub range: Range<usize>,
+
+    /// Line of the start of the range, starting at 1.
+    pub line: usize,
+
+    /// Column of the start of the range in characters, starting at 1.
+    pub column: usize,
+}
+
+impl SourceLocation {
+    /// Locate a range of `src`, which was read from `path`.
+    pub fn new(src: &str, path: Option<PathBuf>, range: TextRange) -> Self {
+        let range = usize::from(range.start())..usize::from(range.end());
+        let (line, column) = line_column(src, range.start);
+        SourceLocation {
+            path,
+            range,
+            line,
+            column,
+        }
+    }
+
+    /// Render the location as a Markdown link, e.g. `[lib/strings.nix:42](url)`.
+    ///
+    /// The url is created from `template` by replacing `{path}`, `{line}`
+    /// and `{column}` with this location.
+    pub fn format_link(&self, template: &str) -> String {
+        let path = self
+            .path
+            .as_ref()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        let path = path.strip_prefix("./").unwrap_or(&path);
+        let url = template
+            .replace("{path}", path)
+            .replace("{line}", &self.line.to_string())
+            .replace("{column}", &self.column.to_string());
+        format!("[{}:{}]({})", path, self.line, url)
+    }
+
+    /// Returns `path:line:column`, or `line:column` if the path is unknown.
+    pub fn display_position(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{}:{}:{}", path.display(), self.line, self.column),
+            None => format!("{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// The lines of a source, for locating many offsets in it without
+/// scanning the source again for each of them.
+#[derive(Debug)]
+pub struct LineIndex {
+    src: String,
+
+    /// Byte offsets of the starts of all lines.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(src: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex {
+            src: src.to_string(),
+            line_starts,
+        }
+    }
+
+    /// Returns the line and column (both starting at 1) of a byte offset,
+    /// see [line_column].
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = floor_char_boundary(&self.src, offset);
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self.src[line_start..offset].chars().count() + 1;
+        (line, column)
+    }
+
+    /// Locate a syntax node of the source, which was read from `path`.
+    pub fn locate(&self, node: &SyntaxNode, path: Option<PathBuf>) -> SourceLocation {
+        let range = node.text_range();
+        let range = usize::from(range.start())..usize::from(range.end());
+        let (line, column) = self.line_column(range.start);
+        SourceLocation {
+            path,
+            range,
+            line,
+            column,
+        }
+    }
+}
+
+/// Returns the line and column (both starting at 1) of a byte offset.
+/// Offsets past the end of `src` are clamped to its end.
+pub fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let offset = floor_char_boundary(src, offset);
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = src[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+/// Returns the text of the line containing a byte offset, without its
+/// line break.
+pub fn line_text(src: &str, offset: usize) -> &str {
+    let offset = floor_char_boundary(src, offset);
+    let start = src[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = src[offset..].find('\n').map_or(src.len(), |i| offset + i);
+    &src[start..end]
+}
+
+fn floor_char_boundary(src: &str, offset: usize) -> usize {
+    let mut offset = offset.min(src.len());
+    while !src.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
@@ -16,41 +16,61 @@
 //! This tool generates CommonMark from a Nix file defining library
 //! functions, such as the files in `lib/` in the nixpkgs repository.
 //!
-//! TODO:
-//! * extract function argument names
-//! * extract line number & add it to generated output
-//! * figure out how to specify examples (& leading whitespace?!)
-
-mod comment;
-mod commonmark;
-mod format;
-mod legacy;
-#[cfg(test)]
-mod test;
-
-use crate::{format::handle_indentation, legacy::retrieve_legacy_comment};
+//! Without a subcommand it renders a file, or with `--follow-imports` every
+//! file imported by a library entry file, as CommonMark, HTML, man pages,
+//! JSON or a search index. The subcommands work on the same files:
+//!
+//! * `build` and `watch` document the files listed by a configuration file
+//! * `show` prints the documentation of a single entry in the terminal
+//! * `coverage` reports which bindings and arguments are documented
+//! * `lint` checks doc comments against the code and the anchors they define
+//! * `extract-examples` turns usage examples into tests for `lib.runTests`
+//! * `migrate` rewrites legacy comments into RFC145 doc comments
+//! * `json-schema` prints the JSON Schema of the JSON output
 
-use self::comment::get_expr_docs;
-use self::commonmark::*;
-use format::shift_headings;
-use legacy::{collect_lambda_args_legacy, LegacyDocItem};
-use rnix::{
-    ast::{Attr, AttrpathValue, Expr, Inherit, LetIn},
-    SyntaxKind, SyntaxNode,
+use nixdoc::{
+    anchors::{validate_anchors, Document},
+    cache::Cache,
+    collect_entries,
+    config::{Config, Overrides, Target},
+    coverage::{collect_coverage, Coverage},
+    dependencies,
+    examples::{self, check_examples, render_tests},
+    html::{render_site, SiteCategory},
+    imports::collect_imports,
+    json_schema,
+    links::{link_references, read_link_index, LinkIndex},
+    lint::lint,
+    location::LineIndex,
+    man::render_man_pages,
+    migrate::migrate,
+    parse_file, read_locs, render_commonmark, render_html, render_json, render_man,
+    render_search_index, retrieve_description,
+    show::{find_entry, render_entry},
+    CollectOptions, Format, JsonVersion, ManualEntry, NixdocError,
 };
-use rowan::{ast::AstNode, WalkEvent};
+use std::collections::{BTreeSet, HashSet};
+use std::env;
 use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
-use serde::Serialize;
-use std::collections::HashMap;
-
-use clap::Parser;
+use clap::{ColorChoice, Parser, Subcommand};
+use notify::{EventKind, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use similar::TextDiff;
 use std::path::PathBuf;
 
 /// Command line arguments for nixdoc
 #[derive(Debug, Parser)]
-#[command(author, version, about)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
 struct Options {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Prefix for the category (e.g. 'lib' or 'utils').
     #[arg(short, long, default_value_t = String::from("lib"))]
     prefix: String,
@@ -62,309 +82,1994 @@ struct Options {
     #[arg(short, long, default_value_t = false)]
     json_output: bool,
 
+    /// Version of the JSON output format.
+    #[arg(long, default_value_t = JsonVersion::default(), requires = "json_output")]
+    json_version: JsonVersion,
+
+    /// Format of the documentation, 'commonmark', 'html', 'man' or
+    /// 'search-index'.
+    #[arg(long, default_value_t = Format::default(), conflicts_with = "json_output")]
+    format: Format,
+
+    /// With --format search-index, include an inverted index mapping tokens
+    /// to the entries containing them.
+    #[arg(long, default_value_t = false)]
+    inverted_index: bool,
+
+    /// Write a standalone HTML site into this directory: an index page, a page
+    /// per category with a sidebar of its entries, and a stylesheet.
+    #[arg(long, conflicts_with_all = ["json_output", "output_dir"])]
+    site: Option<PathBuf>,
+
+    /// Write a man page per category and per function into DIR/man3, so that
+    /// e.g. `MANPATH=DIR man lib.strings.concatStrings` shows a function.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["json_output", "output_dir", "site"])]
+    man_dir: Option<PathBuf>,
+
     /// Name of the function category (e.g. 'strings', 'attrsets').
-    #[arg(short, long)]
+    #[arg(short, long, default_value_t = String::new())]
     category: String,
 
     /// Description of the function category.
-    #[arg(short, long)]
+    #[arg(short, long, default_value_t = String::new())]
     description: String,
 
     /// Nix file to process.
-    #[arg(short, long)]
-    file: PathBuf,
+    #[arg(short, long, required = true)]
+    file: Option<PathBuf>,
+
+    /// Treat the file as the entry point of a library (e.g. 'lib/default.nix')
+    /// and document every file it imports as a category named after its attribute.
+    #[arg(long, default_value_t = false)]
+    follow_imports: bool,
+
+    /// With --follow-imports, write one document per category into this
+    /// directory instead of printing a combined document.
+    #[arg(short, long, requires = "follow_imports")]
+    output_dir: Option<PathBuf>,
+
+    /// Link inline code spans naming a documented entry (e.g. `concatStrings`) to that entry.
+    #[arg(long, default_value_t = false)]
+    cross_links: bool,
+
+    /// With --cross-links, also link to the targets of this JSON file, which maps
+    /// names (e.g. 'lib.attrsets.mapAttrs') to links.
+    #[arg(long, requires = "cross_links")]
+    link_index: Option<PathBuf>,
 
     /// Path to a file containing location data as JSON.
+    /// Takes precedence over --source-url-template.
     #[arg(short, long)]
     locs: Option<PathBuf>,
+
+    /// Link entries to their source, e.g. 'https://example.com/{path}#L{line}'.
+    /// '{path}', '{line}' and '{column}' are replaced by the location of the entry.
+    #[arg(long)]
+    source_url_template: Option<String>,
+
+    /// Maximum depth of nested attribute sets to document.
+    /// The default of 1 only documents the top-level attribute set.
+    #[arg(long, default_value_t = 1)]
+    max_depth: usize,
 }
 
-#[derive(Debug)]
-struct DocComment {
-    /// Primary documentation string.
-    doc: String,
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the JSON Schema of the JSON output.
+    JsonSchema {
+        /// Version of the JSON output format.
+        #[arg(long, default_value_t = JsonVersion::default())]
+        json_version: JsonVersion,
+    },
+
+    /// Report which bindings of a file, and which of their arguments, are documented.
+    Coverage {
+        /// Nix file to process.
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Name of the function category (e.g. 'strings', 'attrsets').
+        #[arg(short, long, default_value_t = String::new())]
+        category: String,
+
+        /// Treat the file as the entry point of a library and report the
+        /// coverage of every file it imports.
+        #[arg(long, default_value_t = false)]
+        follow_imports: bool,
+
+        /// Whether to output JSON.
+        #[arg(short, long, default_value_t = false)]
+        json_output: bool,
+
+        /// Fail if less than this percentage of bindings is documented.
+        #[arg(long, value_name = "PERCENT")]
+        fail_under: Option<f64>,
+    },
+
+    /// Check that the arguments documented by doc comments match the parameters of their lambdas.
+    Lint {
+        /// Nix file to process.
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Prefix for the category (e.g. 'lib' or 'utils').
+        #[arg(short, long, default_value_t = String::from("lib"))]
+        prefix: String,
+
+        #[arg(long, default_value_t = String::from("function-library-"))]
+        anchor_prefix: String,
+
+        /// Name of the function category (e.g. 'strings', 'attrsets').
+        #[arg(short, long, default_value_t = String::new())]
+        category: String,
+
+        /// Treat the file as the entry point of a library and lint every
+        /// file it imports.
+        #[arg(long, default_value_t = false)]
+        follow_imports: bool,
+
+        /// Exit with code 1 if any warnings are reported.
+        #[arg(long, default_value_t = false)]
+        deny_warnings: bool,
+    },
+
+    /// Extract usage examples of the form `expr => result` into tests for `lib.runTests`.
+    ExtractExamples {
+        /// Nix file to process.
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Prefix for the category (e.g. 'lib' or 'utils').
+        #[arg(short, long, default_value_t = String::from("lib"))]
+        prefix: String,
+
+        /// Name of the function category (e.g. 'strings', 'attrsets').
+        #[arg(short, long, default_value_t = String::new())]
+        category: String,
+
+        /// Treat the file as the entry point of a library and extract the
+        /// examples of every file it imports.
+        #[arg(long, default_value_t = false)]
+        follow_imports: bool,
+
+        /// With --follow-imports, write one test file per category into this
+        /// directory instead of printing a combined test file.
+        #[arg(short, long, requires = "follow_imports")]
+        output_dir: Option<PathBuf>,
+    },
+
+    /// Print the documentation of a single entry, e.g. 'strings.splitString'.
+    Show {
+        /// Attribute path of the entry, e.g. 'lib.strings.splitString',
+        /// 'strings.splitString' or 'splitString'.
+        path: String,
+
+        /// Nix file to process, or a directory whose 'default.nix' is the
+        /// entry point of a library.
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Prefix for the category (e.g. 'lib' or 'utils').
+        #[arg(short, long, default_value_t = String::from("lib"))]
+        prefix: String,
+
+        /// Name of the function category (e.g. 'strings', 'attrsets').
+        /// Defaults to the name of the file.
+        #[arg(short, long, default_value_t = String::new())]
+        category: String,
+
+        /// Treat the file as the entry point of a library and look up the
+        /// entry in every file it imports.
+        #[arg(long, default_value_t = false)]
+        follow_imports: bool,
+
+        /// When to style the output with colors.
+        #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+        color: ColorChoice,
+    },
+
+    /// Document every file listed by a configuration file in a single run.
+    Build {
+        /// Configuration file listing the files to document.
+        #[arg(long, default_value = "nixdoc.toml")]
+        config: PathBuf,
+
+        /// Prefix for the categories of all files, overriding the configuration.
+        #[arg(short, long)]
+        prefix: Option<String>,
+
+        /// Prefix of the anchors of all files, overriding the configuration.
+        #[arg(long)]
+        anchor_prefix: Option<String>,
 
-    /// Optional type annotation for the thing being documented.
-    /// This is only available as legacy feature
-    doc_type: Option<String>,
+        /// Format of the documentation of all files, overriding the configuration.
+        #[arg(long)]
+        format: Option<Format>,
 
-    /// Usage example(s) (interpreted as a single code block)
-    /// This is only available as legacy feature
-    example: Option<String>,
+        /// Directory of the cache of rendered files. Defaults to
+        /// '.nixdoc-cache' next to the configuration file.
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+
+        /// Discard the cache before building.
+        #[arg(long, default_value_t = false)]
+        clean: bool,
+
+        /// Print how long each file took, and whether it was cached.
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+
+    /// Watch the files listed by a configuration file, and document and
+    /// lint them again whenever they change.
+    Watch {
+        /// Configuration file listing the files to document.
+        #[arg(long, default_value = "nixdoc.toml")]
+        config: PathBuf,
+
+        /// Prefix for the categories of all files, overriding the configuration.
+        #[arg(short, long)]
+        prefix: Option<String>,
+
+        /// Prefix of the anchors of all files, overriding the configuration.
+        #[arg(long)]
+        anchor_prefix: Option<String>,
+
+        /// Format of the documentation of all files, overriding the configuration.
+        #[arg(long)]
+        format: Option<Format>,
+
+        /// Directory of the cache of rendered files. Defaults to
+        /// '.nixdoc-cache' next to the configuration file.
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
+
+    /// Migrate legacy doc comments to RFC145 doc comments, printing the migrated file.
+    Migrate {
+        /// Nix file to process.
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Rewrite the file in place.
+        #[arg(short, long, default_value_t = false, conflicts_with = "diff")]
+        write: bool,
+
+        /// Print a unified diff of the changes instead of the migrated file.
+        #[arg(long, default_value_t = false)]
+        diff: bool,
+
+        /// Maximum depth of nested attribute sets to migrate, as for
+        /// rendering.
+        #[arg(long, default_value_t = 1)]
+        max_depth: usize,
+    },
 }
 
-#[derive(Debug)]
-struct DocItem {
-    name: String,
-    comment: DocComment,
+/// The output of a successful run.
+struct Output {
+    /// Text to print.
+    text: String,
+
+    /// Diagnostics to print to stderr, which do not fail the run.
+    diagnostics: Vec<String>,
+
+    /// Why a check failed, if it did. The text is still printed, but
+    /// nixdoc exits with code 1.
+    failure: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-struct JsonFormat {
-    version: u32,
-    entries: Vec<ManualEntry>,
+impl From<String> for Output {
+    fn from(text: String) -> Self {
+        Output {
+            text,
+            diagnostics: vec![],
+            failure: None,
+        }
+    }
 }
 
-enum DocItemOrLegacy {
-    LegacyDocItem(LegacyDocItem),
-    DocItem(DocItem),
+impl Options {
+    /// The options documenting a file of `nixdoc build` as the command
+    /// line would with the same settings.
+    fn for_target(target: &Target) -> Options {
+        Options {
+            command: None,
+            prefix: target.prefix.clone(),
+            anchor_prefix: target.anchor_prefix.clone(),
+            json_output: false,
+            json_version: JsonVersion::default(),
+            format: target.format,
+            inverted_index: false,
+            site: None,
+            man_dir: None,
+            category: target.category.clone(),
+            description: target.description.clone(),
+            file: Some(target.path.clone()),
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            locs: target.locs.clone(),
+            source_url_template: target.source_url_template.clone(),
+            max_depth: target.max_depth,
+        }
+    }
+
+    /// The Nix file to process, which is required unless a subcommand is given.
+    fn file(&self) -> &Path {
+        self.file
+            .as_deref()
+            .expect("--file is required without a subcommand")
+    }
+
+    /// The name of the man page of a category, e.g. `lib.strings`.
+    fn man_name(&self, category: &Category) -> String {
+        [self.prefix.as_str(), category.name.as_str()]
+            .into_iter()
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn collect_options(&self, category: &str, file: &Path) -> Result<CollectOptions, NixdocError> {
+        Ok(CollectOptions {
+            prefix: self.prefix.clone(),
+            category: category.to_string(),
+            locs: match &self.locs {
+                None => Default::default(),
+                Some(p) => read_locs(p)?,
+            },
+            file: Some(file.to_path_buf()),
+            max_depth: self.max_depth,
+            source_url_template: self.source_url_template.clone(),
+            // Only source links and the spans of JSON v2 show locations.
+            spans: self.source_url_template.is_some()
+                || (self.json_output && self.json_version == JsonVersion::V2),
+            lines: None,
+        })
+    }
 }
 
-/// Returns a rfc145 doc-comment if one is present
-pub fn retrieve_doc_comment(node: &SyntaxNode, shift_headings_by: Option<usize>) -> Option<String> {
-    let doc_comment = get_expr_docs(node);
+/// The documented entries of a category and its rendered heading.
+struct Category {
+    /// Name of the category, or of its file if it has none.
+    name: String,
+    title: String,
+    description: String,
+    entries: Vec<ManualEntry>,
+}
 
-    doc_comment.map(|doc_comment| {
-        shift_headings(
-            &handle_indentation(&doc_comment).unwrap_or(String::new()),
-            // H1 to H4 can be used in the doc-comment with the current rendering.
-            // They will be shifted to H3, H6
-            // H1 and H2 are currently used by the outer rendering. (category and function name)
-            shift_headings_by.unwrap_or(2),
-        )
+/// Collect the entries of a single file as one category.
+fn collect_category(
+    opts: &Options,
+    file: &Path,
+    category: &str,
+    description: &str,
+) -> Result<Category, NixdocError> {
+    let nix = parse_file(file)?;
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = if category.is_empty() {
+        stem
+    } else {
+        category.to_string()
+    };
+    Ok(Category {
+        title: if description.is_empty() {
+            name.clone()
+        } else {
+            description.to_string()
+        },
+        name,
+        description: retrieve_description(&nix, description, category),
+        entries: collect_entries(nix, &opts.collect_options(category, file)?),
     })
 }
 
-/// Transforms an AST node into a `DocItem` if it has a leading
-/// documentation comment.
-fn retrieve_doc_item(node: &AttrpathValue) -> Option<DocItemOrLegacy> {
-    let ident = node.attrpath().unwrap();
-    // TODO this should join attrs() with '.' to handle whitespace, dynamic attrs and string
-    // attrs. none of these happen in nixpkgs lib, and the latter two should probably be
-    // rejected entirely.
-    let item_name = ident.to_string();
-
-    let doc_comment = retrieve_doc_comment(node.syntax(), Some(2));
-    match doc_comment {
-        Some(comment) => Some(DocItemOrLegacy::DocItem(DocItem {
-            name: item_name,
-            comment: DocComment {
-                doc: comment,
-                doc_type: None,
-                example: None,
-            },
-        })),
-        // Fallback to legacy comment is there is no doc_comment
-        None => {
-            let comment = retrieve_legacy_comment(node.syntax(), false)?;
-            Some(DocItemOrLegacy::LegacyDocItem(LegacyDocItem {
-                name: item_name,
-                comment: parse_doc_comment(&comment),
-                args: vec![],
-            }))
+/// With --cross-links, link the references between all entries of the
+/// given categories, and to the external link index if there is one.
+fn cross_link(opts: &Options, categories: &mut [Category]) -> Result<(), NixdocError> {
+    if !opts.cross_links {
+        return Ok(());
+    }
+    let mut index = LinkIndex::new(
+        categories.iter().flat_map(|category| &category.entries),
+        &opts.anchor_prefix,
+    );
+    if let Some(path) = &opts.link_index {
+        index.extend_external(read_link_index(path)?);
+    }
+    for category in categories {
+        link_references(&mut category.entries, &index, &opts.anchor_prefix);
+    }
+    Ok(())
+}
+
+/// Render categories into a single document.
+fn render(opts: &Options, categories: Vec<Category>) -> Result<String, NixdocError> {
+    if opts.json_output {
+        let entries = categories
+            .into_iter()
+            .flat_map(|category| category.entries)
+            .collect();
+        render_json(entries, opts.json_version)
+    } else if opts.format == Format::SearchIndex {
+        let entries: Vec<ManualEntry> = categories
+            .into_iter()
+            .flat_map(|category| category.entries)
+            .collect();
+        render_search_index(&entries, &opts.anchor_prefix, opts.inverted_index)
+    } else {
+        Ok(categories
+            .into_iter()
+            .map(|category| match opts.format {
+                Format::CommonMark => {
+                    render_commonmark(&category.description, category.entries, &opts.anchor_prefix)
+                }
+                Format::Html => {
+                    render_html(&category.description, category.entries, &opts.anchor_prefix)
+                }
+                Format::Man => render_man(
+                    &opts.man_name(&category),
+                    &category.description,
+                    category.entries,
+                ),
+                Format::SearchIndex => unreachable!("the search index covers all categories"),
+            })
+            .collect())
+    }
+}
+
+/// Write a static HTML site of the categories into a directory.
+fn write_site(
+    opts: &Options,
+    dir: &Path,
+    categories: Vec<Category>,
+) -> Result<String, NixdocError> {
+    let title = [&opts.description, &opts.prefix]
+        .into_iter()
+        .find(|title| !title.is_empty())
+        .map_or("Documentation", String::as_str);
+    let categories: Vec<SiteCategory> = categories
+        .into_iter()
+        .map(|category| SiteCategory {
+            name: category.name,
+            title: category.title,
+            description: category.description,
+            entries: category.entries,
+        })
+        .collect();
+
+    fs::create_dir_all(dir).map_err(|source| NixdocError::Io {
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    for (name, contents) in render_site(title, &categories, &opts.anchor_prefix) {
+        let path = dir.join(name);
+        fs::write(&path, contents).map_err(|source| NixdocError::Io { path, source })?;
+    }
+    Ok(String::new())
+}
+
+/// Write a man page per category and per function into the `man3`
+/// directory of `dir`.
+fn write_man_pages(
+    opts: &Options,
+    dir: &Path,
+    categories: Vec<Category>,
+) -> Result<String, NixdocError> {
+    let dir = dir.join("man3");
+    fs::create_dir_all(&dir).map_err(|source| NixdocError::Io {
+        path: dir.clone(),
+        source,
+    })?;
+    for category in categories {
+        let mut pages = render_man_pages(&category.entries);
+        let name = opts.man_name(&category);
+        pages.push((
+            format!("{}.3", name),
+            render_man(&name, &category.description, category.entries),
+        ));
+        for (name, contents) in pages {
+            let path = dir.join(name);
+            fs::write(&path, contents).map_err(|source| NixdocError::Io { path, source })?;
         }
     }
+    Ok(String::new())
 }
 
-/// Dumb, mutable, hacky doc comment "parser".
-fn parse_doc_comment(raw: &str) -> DocComment {
-    enum ParseState {
-        Doc,
-        Type,
-        Example,
+/// Document every category imported by the library entry file.
+///
+/// Categories are rendered into a combined document, or into one file
+/// per category if an output directory is given.
+fn render_imports(opts: &Options) -> Result<String, NixdocError> {
+    let nix = parse_file(opts.file())?;
+    let mut categories = collect_imports(&nix, opts.file())
+        .iter()
+        .map(|import| {
+            let description = import.description.as_deref().unwrap_or(&import.category);
+            collect_category(opts, &import.file, &import.category, description)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    cross_link(opts, &mut categories)?;
+    if let Some(dir) = &opts.site {
+        return write_site(opts, dir, categories);
+    }
+    if let Some(dir) = &opts.man_dir {
+        return write_man_pages(opts, dir, categories);
     }
 
-    let mut state = ParseState::Doc;
+    let Some(dir) = &opts.output_dir else {
+        return render(opts, categories);
+    };
+    fs::create_dir_all(dir).map_err(|source| NixdocError::Io {
+        path: dir.clone(),
+        source,
+    })?;
+    let extension = match opts.format {
+        _ if opts.json_output => "json",
+        Format::CommonMark => "md",
+        Format::Html => "html",
+        Format::Man => "3",
+        Format::SearchIndex => {
+            // A single index covers all categories.
+            let path = dir.join("search-index.json");
+            let output = render(opts, categories)?;
+            fs::write(&path, output).map_err(|source| NixdocError::Io { path, source })?;
+            return Ok(String::new());
+        }
+    };
+    for category in categories {
+        let path = dir.join(format!("{}.{}", category.name, extension));
+        let output = render(opts, vec![category])?;
+        fs::write(&path, output).map_err(|source| NixdocError::Io { path, source })?;
+    }
+    Ok(String::new())
+}
 
-    // Split the string into three parts, docs, type and example
-    let mut doc_str = String::new();
-    let mut type_str = String::new();
-    let mut example_str = String::new();
+/// Document a single file as one category.
+fn render_file(opts: &Options) -> Result<String, NixdocError> {
+    let mut categories = vec![collect_category(
+        opts,
+        opts.file(),
+        &opts.category,
+        &opts.description,
+    )?];
+    cross_link(opts, &mut categories)?;
+    match (&opts.site, &opts.man_dir) {
+        (Some(dir), _) => write_site(opts, dir, categories),
+        (_, Some(dir)) => write_man_pages(opts, dir, categories),
+        (None, None) => render(opts, categories),
+    }
+}
 
-    for line in raw.split_inclusive('\n') {
-        let trimmed_line = line.trim();
-        if let Some(suffix) = trimmed_line.strip_prefix("Type:") {
-            state = ParseState::Type;
-            type_str.push_str(suffix);
-            type_str.push('\n');
-        } else if let Some(suffix) = trimmed_line.strip_prefix("Example:") {
-            state = ParseState::Example;
-            example_str.push_str(suffix);
-            example_str.push('\n');
-        } else {
-            match state {
-                ParseState::Doc => doc_str.push_str(line),
-                ParseState::Type => type_str.push_str(line),
-                ParseState::Example => example_str.push_str(line),
+/// Report the documentation coverage of a file, or of every file imported
+/// by a library entry file.
+fn report_coverage(
+    file: &Path,
+    category: &str,
+    follow_imports: bool,
+    json_output: bool,
+    fail_under: Option<f64>,
+) -> Result<Output, NixdocError> {
+    let coverage_of = |file: &Path, category: &str| -> Result<Coverage, NixdocError> {
+        let opts = CollectOptions {
+            category: category.to_string(),
+            file: Some(file.to_path_buf()),
+            ..Default::default()
+        };
+        Ok(collect_coverage(&parse_file(file)?, &opts))
+    };
+
+    let coverage = if follow_imports {
+        let mut coverage = Coverage::default();
+        for import in collect_imports(&parse_file(file)?, file) {
+            let imported = coverage_of(&import.file, &import.category)?;
+            coverage.bindings.extend(imported.bindings);
+        }
+        coverage
+    } else {
+        coverage_of(file, category)?
+    };
+
+    let text = if json_output {
+        coverage.render_json().map_err(NixdocError::Json)?
+    } else {
+        coverage.render_text()
+    };
+    let failure = fail_under
+        .filter(|threshold| coverage.percentage() < *threshold)
+        .map(|threshold| {
+            format!(
+                "documentation coverage of {:.2}% is below {}%",
+                coverage.percentage(),
+                threshold
+            )
+        });
+    Ok(Output {
+        text,
+        diagnostics: vec![],
+        failure,
+    })
+}
+
+/// Lint a file, or every file imported by a library entry file, check
+/// that their usage examples parse and validate the anchors of their
+/// documentation.
+fn report_lints(
+    file: &Path,
+    prefix: &str,
+    anchor_prefix: &str,
+    category: &str,
+    follow_imports: bool,
+    deny_warnings: bool,
+) -> Result<Output, NixdocError> {
+    let categories = if follow_imports {
+        collect_imports(&parse_file(file)?, file)
+            .into_iter()
+            .map(|import| {
+                let description = import.description.unwrap_or(import.category.clone());
+                (import.file, import.category, description)
+            })
+            .collect()
+    } else {
+        vec![(file.to_path_buf(), category.to_string(), String::new())]
+    };
+
+    let mut diagnostics = vec![];
+    let mut documents = vec![];
+    for (file, category, description) in &categories {
+        let nix = parse_file(file)?;
+        let src = nix.to_string();
+        let lines = Arc::new(LineIndex::new(&src));
+        diagnostics.extend(lint(&nix, Some(file), 1, &lines));
+        let opts = CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.clone(),
+            file: Some(file.clone()),
+            lines: Some(lines),
+            ..Default::default()
+        };
+        let description = retrieve_description(&nix, description, category);
+        let entries = collect_entries(nix, &opts);
+        diagnostics.extend(check_examples(&src, Some(file), &entries));
+        documents.push((file, description, entries));
+    }
+    let documents: Vec<Document> = documents
+        .iter()
+        .map(|(file, description, entries)| Document {
+            file: Some(file),
+            description,
+            entries,
+        })
+        .collect();
+    // Links to other categories can only be checked against the whole
+    // library.
+    let link_scope = (!follow_imports).then(|| {
+        let category = [prefix, category]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(".");
+        format!("{}{}.", anchor_prefix, category)
+    });
+    diagnostics.extend(validate_anchors(
+        &documents,
+        anchor_prefix,
+        link_scope.as_deref(),
+    ));
+
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    let errors = diagnostics.iter().filter(|d| d.lint.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    let failure = if errors > 0 {
+        Some(format!("{} error{} found", errors, plural(errors)))
+    } else if deny_warnings && warnings > 0 {
+        Some(format!(
+            "{} warning{} denied by --deny-warnings",
+            warnings,
+            plural(warnings)
+        ))
+    } else {
+        None
+    };
+    Ok(Output {
+        text: String::new(),
+        diagnostics: diagnostics.iter().map(ToString::to_string).collect(),
+        failure,
+    })
+}
+
+/// Extract the usage examples of a file, or of every file imported by a
+/// library entry file, into tests for `lib.runTests`.
+fn extract_examples(
+    file: &Path,
+    prefix: &str,
+    category: &str,
+    follow_imports: bool,
+    output_dir: Option<&Path>,
+) -> Result<Output, NixdocError> {
+    let categories = if follow_imports {
+        collect_imports(&parse_file(file)?, file)
+            .into_iter()
+            .map(|import| (import.file, import.category))
+            .collect()
+    } else {
+        vec![(file.to_path_buf(), category.to_string())]
+    };
+
+    let mut tests = vec![];
+    let mut diagnostics = vec![];
+    for (file, category) in categories {
+        let nix = parse_file(&file)?;
+        let src = nix.to_string();
+        let opts = CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.clone(),
+            file: Some(file.clone()),
+            ..Default::default()
+        };
+        let examples = examples::extract_examples(&src, Some(&file), &collect_entries(nix, &opts));
+        diagnostics.extend(examples.diagnostics.iter().map(|d| d.to_string()));
+        tests.push((category, examples.examples));
+    }
+
+    let text = match output_dir {
+        None => render_tests(
+            &tests
+                .into_iter()
+                .flat_map(|(_, tests)| tests)
+                .collect::<Vec<_>>(),
+        ),
+        Some(dir) => {
+            fs::create_dir_all(dir).map_err(|source| NixdocError::Io {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+            for (category, tests) in tests {
+                let path = dir.join(format!("{}.nix", category));
+                fs::write(&path, render_tests(&tests))
+                    .map_err(|source| NixdocError::Io { path, source })?;
             }
+            String::new()
         }
+    };
+    Ok(Output {
+        text,
+        diagnostics,
+        failure: None,
+    })
+}
+
+/// Look up a single entry of a file, or of every file imported by a
+/// library entry file, and render it for the terminal.
+fn show_entry(
+    path: &str,
+    file: &Path,
+    prefix: &str,
+    category: &str,
+    follow_imports: bool,
+    color: ColorChoice,
+) -> Result<String, NixdocError> {
+    // A directory is documented as a library by its entry file.
+    let (file, follow_imports) = if file.is_dir() {
+        (file.join("default.nix"), true)
+    } else {
+        (file.to_path_buf(), follow_imports)
+    };
+    let categories = if follow_imports {
+        collect_imports(&parse_file(&file)?, &file)
+            .into_iter()
+            .map(|import| (import.file, import.category))
+            .collect()
+    } else if category.is_empty() {
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        vec![(file.clone(), stem.into_owned())]
+    } else {
+        vec![(file, category.to_string())]
+    };
+
+    let mut entries = vec![];
+    for (file, category) in categories {
+        let opts = CollectOptions {
+            prefix: prefix.to_string(),
+            category,
+            file: Some(file.clone()),
+            ..Default::default()
+        };
+        entries.extend(collect_entries(parse_file(&file)?, &opts));
+    }
+    let entry = find_entry(&entries, path)?;
+
+    let color = match color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    let rendered = render_entry(entry, textwrap::termwidth(), color);
+    Ok(rendered.trim_end().to_string())
+}
+
+/// A file documented by `nixdoc build`.
+struct Built {
+    text: String,
+
+    /// Whether the documentation was printed rather than written.
+    printed: bool,
+
+    /// Whether the documentation was taken from the cache.
+    cached: bool,
+
+    elapsed: Duration,
+}
+
+impl Built {
+    /// Describe how a file was documented, e.g. `lib/strings.nix: cached in 1.2ms`.
+    fn report(&self, target: &Target) -> String {
+        format!(
+            "{}: {} in {:.1?}",
+            target.path.display(),
+            if self.cached { "cached" } else { "rendered" },
+            self.elapsed
+        )
     }
+}
 
-    DocComment {
-        doc: handle_indentation(&doc_str).unwrap_or(String::new()),
-        doc_type: handle_indentation(&type_str),
-        example: handle_indentation(&example_str),
+/// The cache directory of `nixdoc build`, which defaults to
+/// `.nixdoc-cache` next to the configuration file.
+fn default_cache_dir(config: &Path, cache_dir: Option<&Path>) -> PathBuf {
+    match cache_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => config
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(".nixdoc-cache"),
     }
 }
 
-/// Traverse the arena from a top-level SetEntry and collect, where
-/// possible:
+/// Document every file listed by a configuration file in parallel,
+/// writing the documentation of each file to its output, or printing it.
 ///
-/// 1. The identifier of the set entry itself.
-/// 2. The attached doc comment on the entry.
-/// 3. The argument names of any curried functions (pattern functions
-///    not yet supported).
-fn collect_entry_information(entry: AttrpathValue) -> Option<LegacyDocItem> {
-    let doc_item = retrieve_doc_item(&entry)?;
-
-    match doc_item {
-        DocItemOrLegacy::LegacyDocItem(v) => {
-            if let Some(Expr::Lambda(l)) = entry.value() {
-                Some(LegacyDocItem {
-                    args: collect_lambda_args_legacy(l),
-                    ..v
-                })
-            } else {
-                Some(v)
+/// Rendered files are cached, so that files which did not change since
+/// the last build are not parsed and rendered again.
+fn build(
+    config: &Path,
+    overrides: Overrides,
+    cache_dir: Option<&Path>,
+    clean: bool,
+    verbose: bool,
+) -> Result<Output, NixdocError> {
+    let start = Instant::now();
+    let cache = Cache::open(&default_cache_dir(config, cache_dir), clean)?;
+    let targets = Config::load(config)?.targets(&overrides);
+    let built = targets
+        .par_iter()
+        .map(|target| build_target(target, &cache))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut diagnostics = vec![];
+    if verbose {
+        for (target, built) in targets.iter().zip(&built) {
+            diagnostics.push(built.report(target));
+        }
+        diagnostics.push(format!(
+            "documented {} files ({} cached) in {:.1?}",
+            built.len(),
+            built.iter().filter(|built| built.cached).count(),
+            start.elapsed()
+        ));
+    }
+    Ok(Output {
+        text: built
+            .into_iter()
+            .filter(|built| built.printed)
+            .map(|built| built.text)
+            .collect(),
+        diagnostics,
+        failure: None,
+    })
+}
+
+/// The cache key of the documentation of a file of `nixdoc build`. Besides
+/// the file and its settings, it covers its locations file and the files
+/// its `inherit (source)` clauses may import, whose documentation is
+/// rendered too.
+fn target_key(target: &Target, src: Vec<u8>) -> String {
+    let mut parts = vec![
+        src,
+        target.category.clone().into_bytes(),
+        target.description.clone().into_bytes(),
+        target.prefix.clone().into_bytes(),
+        target.anchor_prefix.clone().into_bytes(),
+        target.format.to_string().into_bytes(),
+        target.max_depth.to_string().into_bytes(),
+        target
+            .source_url_template
+            .clone()
+            .unwrap_or_default()
+            .into_bytes(),
+    ];
+    if let Some(locs) = &target.locs {
+        parts.push(fs::read(locs).unwrap_or_default());
+    }
+    for dependency in dependencies(&target.path) {
+        parts.push(dependency.to_string_lossy().into_owned().into_bytes());
+        parts.push(fs::read(&dependency).unwrap_or_default());
+    }
+    Cache::key(&parts.iter().map(Vec::as_slice).collect::<Vec<_>>())
+}
+
+/// Document a single file of `nixdoc build`, unless it is cached.
+fn build_target(target: &Target, cache: &Cache) -> Result<Built, NixdocError> {
+    let start = Instant::now();
+    let src = fs::read(&target.path).map_err(|source| NixdocError::Io {
+        path: target.path.clone(),
+        source,
+    })?;
+    let key = target_key(target, src);
+
+    let (text, cached) = match cache.get(&key) {
+        Some(text) => (text, true),
+        None => {
+            let text = render_file(&Options::for_target(target))?;
+            cache.put(&key, &text)?;
+            (text, false)
+        }
+    };
+
+    let printed = target.output.is_none();
+    if let Some(path) = &target.output {
+        // Unchanged outputs are not written again, so that their
+        // modification time is kept.
+        if fs::read_to_string(path).ok().as_deref() != Some(text.as_str()) {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|source| NixdocError::Io {
+                    path: dir.to_path_buf(),
+                    source,
+                })?;
             }
+            fs::write(path, &text).map_err(|source| NixdocError::Io {
+                path: path.clone(),
+                source,
+            })?;
         }
-        // Convert DocItems into legacyItem for markdown rendering
-        DocItemOrLegacy::DocItem(v) => Some(LegacyDocItem {
-            args: vec![],
-            name: v.name,
-            comment: v.comment,
-        }),
     }
+    Ok(Built {
+        text,
+        printed,
+        cached,
+        elapsed: start.elapsed(),
+    })
 }
 
-// a binding is an assignment, which can take place in an attrset
-// - as attributes
-// - as inherits
-fn collect_bindings(
-    node: &SyntaxNode,
-    prefix: &str,
-    category: &str,
-    locs: &HashMap<String, String>,
-    scope: HashMap<String, ManualEntry>,
-) -> Vec<ManualEntry> {
-    for ev in node.preorder() {
-        match ev {
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
-                let mut entries = vec![];
-                for child in n.children() {
-                    if let Some(apv) = AttrpathValue::cast(child.clone()) {
-                        entries.extend(
-                            collect_entry_information(apv)
-                                .map(|di| di.into_entry(prefix, category, locs)),
-                        );
-                    } else if let Some(inh) = Inherit::cast(child) {
-                        // `inherit (x) ...` needs much more handling than we can
-                        // reasonably do here
-                        if inh.from().is_some() {
-                            continue;
-                        }
-                        entries.extend(inh.attrs().filter_map(|a| match a {
-                            Attr::Ident(i) => scope.get(&i.syntax().text().to_string()).cloned(),
-                            // ignore non-ident keys. these aren't useful as lib
-                            // functions in general anyway.
-                            _ => None,
-                        }));
+/// Lint a file of `nixdoc build` and check its usage examples, as
+/// `nixdoc lint` does.
+fn lint_target(target: &Target) -> Result<Vec<String>, NixdocError> {
+    let nix = parse_file(&target.path)?;
+    let src = nix.to_string();
+    let lines = Arc::new(LineIndex::new(&src));
+    let mut diagnostics = lint(&nix, Some(&target.path), target.max_depth, &lines);
+    let opts = CollectOptions {
+        prefix: target.prefix.clone(),
+        category: target.category.clone(),
+        file: Some(target.path.clone()),
+        max_depth: target.max_depth,
+        lines: Some(lines),
+        ..Default::default()
+    };
+    diagnostics.extend(check_examples(
+        &src,
+        Some(&target.path),
+        &collect_entries(nix, &opts),
+    ));
+    Ok(diagnostics.iter().map(ToString::to_string).collect())
+}
+
+/// How long to wait for further events after a file changed. Editors
+/// often save files in several steps, which are handled at once.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// The state of `nixdoc watch`: the files listed by the configuration,
+/// and the cache they are documented with.
+struct Watch {
+    config: PathBuf,
+    overrides: Overrides,
+    cache: Cache,
+
+    /// The files to document.
+    targets: Vec<Watched>,
+}
+
+/// A file of `nixdoc watch`, with the canonical paths events refer to.
+struct Watched {
+    path: PathBuf,
+
+    /// The files its documentation depends on, see [dependencies].
+    dependencies: BTreeSet<PathBuf>,
+
+    target: Target,
+}
+
+impl Watched {
+    fn new(target: Target) -> Self {
+        let mut watched = Watched {
+            path: canonical(&target.path),
+            dependencies: BTreeSet::new(),
+            target,
+        };
+        watched.update_dependencies();
+        watched
+    }
+
+    /// Find the dependencies again, as the file or its dependencies changed.
+    fn update_dependencies(&mut self) {
+        self.dependencies = dependencies(&self.target.path)
+            .iter()
+            .map(|path| canonical(path))
+            .collect();
+    }
+
+    /// Whether the file or any of its dependencies are among the paths.
+    fn affected_by(&self, paths: &HashSet<PathBuf>) -> bool {
+        paths.contains(&self.path) || self.dependencies.iter().any(|path| paths.contains(path))
+    }
+}
+
+/// Returns the canonical form of a path. Paths which do not exist (anymore)
+/// are canonicalized by their parent directory if it exists, and are
+/// returned as they are otherwise.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| {
+        match (path.parent().map(fs::canonicalize), path.file_name()) {
+            (Some(Ok(dir)), Some(name)) => dir.join(name),
+            _ => path.to_path_buf(),
+        }
+    })
+}
+
+impl Watch {
+    /// Read the configuration file again.
+    fn load(&mut self) -> Result<(), NixdocError> {
+        self.targets = Config::load(&self.config)?
+            .targets(&self.overrides)
+            .into_iter()
+            .map(Watched::new)
+            .collect();
+        Ok(())
+    }
+
+    /// The existing directories containing the configuration file, the
+    /// files to document and their dependencies. Directories are watched
+    /// rather than the files themselves, as editors often replace files
+    /// instead of writing to them.
+    fn directories(&self) -> BTreeSet<PathBuf> {
+        std::iter::once(canonical(&self.config))
+            .chain(self.targets.iter().flat_map(|watched| {
+                std::iter::once(watched.path.clone()).chain(watched.dependencies.iter().cloned())
+            }))
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    /// Document and lint the files affected by the changed paths again,
+    /// i.e. those which changed or whose dependencies changed, or all files
+    /// if the configuration changed. Returns `None` if no file of the
+    /// configuration is affected.
+    fn changed(&mut self, paths: &HashSet<PathBuf>) -> Option<Output> {
+        let all = paths.contains(&canonical(&self.config));
+        if all {
+            if let Err(error) = self.load() {
+                return Some(Output {
+                    text: String::new(),
+                    diagnostics: vec![format!("error: {}", error)],
+                    failure: None,
+                });
+            }
+        }
+        // The imports of affected files may have changed as well.
+        let mut targets: Vec<&Target> = vec![];
+        for watched in &mut self.targets {
+            if all || watched.affected_by(paths) {
+                watched.update_dependencies();
+                targets.push(&watched.target);
+            }
+        }
+        if targets.is_empty() {
+            return None;
+        }
+
+        // Files which fail to build are reported, and watched still.
+        let results: Vec<_> = targets
+            .par_iter()
+            .map(|target| {
+                let built = build_target(target, &self.cache)?;
+                Ok((built, lint_target(target)?))
+            })
+            .collect::<Vec<Result<_, NixdocError>>>();
+        let mut output = Output::from(String::new());
+        for (target, result) in targets.iter().zip(results) {
+            match result {
+                Ok((built, lints)) => {
+                    output.diagnostics.push(built.report(target));
+                    output.diagnostics.extend(lints);
+                    if built.printed {
+                        output.text.push_str(&built.text);
                     }
                 }
-                return entries;
+                Err(error) => output.diagnostics.push(format!("error: {}", error)),
             }
-            _ => (),
         }
+        Some(output)
     }
-
-    vec![]
 }
 
-// Main entrypoint for collection
-// TODO: document
-fn collect_entries(
-    root: rnix::Root,
-    prefix: &str,
-    category: &str,
-    locs: &HashMap<String, String>,
-) -> Vec<ManualEntry> {
-    // we will look into the top-level let and its body for function docs.
-    // we only need a single level of scope for this.
-    // since only the body can export a function we don't need to implement
-    // mutually recursive resolution.
-    let mut preorder = root.syntax().preorder();
-    while let Some(ev) = preorder.next() {
-        match ev {
-            // Skip patterns. See test/patterns.nix for the reason why.
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_PATTERN => {
-                preorder.skip_subtree();
+/// Document and lint the files listed by a configuration file, and do it
+/// again for every file which changes, until interrupted.
+fn watch(
+    config: &Path,
+    overrides: Overrides,
+    cache_dir: Option<&Path>,
+) -> Result<Output, NixdocError> {
+    let mut watch = Watch {
+        config: config.to_path_buf(),
+        overrides,
+        cache: Cache::open(&default_cache_dir(config, cache_dir), false)?,
+        targets: vec![],
+    };
+    watch.load()?;
+    let all = watch
+        .targets
+        .iter()
+        .map(|watched| watched.path.clone())
+        .collect();
+    if let Some(output) = watch.changed(&all) {
+        print(&output);
+    }
+
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(NixdocError::Watch)?;
+    let mut watched = BTreeSet::new();
+    loop {
+        // Files added to the configuration may be in other directories.
+        for dir in watch.directories() {
+            if !watched.contains(&dir) {
+                watcher
+                    .watch(&dir, RecursiveMode::NonRecursive)
+                    .map_err(NixdocError::Watch)?;
+                watched.insert(dir);
             }
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_LET_IN => {
-                return collect_bindings(
-                    LetIn::cast(n.clone()).unwrap().body().unwrap().syntax(),
-                    prefix,
-                    category,
-                    locs,
-                    n.children()
-                        .filter_map(AttrpathValue::cast)
-                        .filter_map(collect_entry_information)
-                        .map(|di| (di.name.to_string(), di.into_entry(prefix, category, locs)))
-                        .collect(),
-                );
+        }
+
+        let Ok(mut event) = events.recv() else {
+            break;
+        };
+        let mut paths = HashSet::new();
+        loop {
+            match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    paths.extend(event.paths.iter().map(|path| canonical(path)));
+                }
+                Ok(_) => {}
+                Err(error) => eprintln!("error: {}", NixdocError::Watch(error)),
             }
-            WalkEvent::Enter(n) if n.kind() == SyntaxKind::NODE_ATTR_SET => {
-                return collect_bindings(&n, prefix, category, locs, Default::default());
+            match events.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(next) => event = next,
+                Err(_) => break,
             }
-            _ => (),
+        }
+        if let Some(output) = watch.changed(&paths) {
+            print(&output);
         }
     }
+    Ok(Output::from(String::new()))
+}
+
+/// Migrate the legacy doc comments of a file to RFC145 doc comments.
+fn migrate_file(
+    file: &Path,
+    write: bool,
+    diff: bool,
+    max_depth: usize,
+) -> Result<String, NixdocError> {
+    let nix = parse_file(file)?;
+    let src = nix.to_string();
+    let migrated = migrate(&nix, max_depth);
 
-    vec![]
+    // The output is printed with a final newline of its own.
+    let printed = |text: String| text.strip_suffix('\n').map(str::to_string).unwrap_or(text);
+    if write {
+        if migrated != src {
+            fs::write(file, &migrated).map_err(|source| NixdocError::Io {
+                path: file.to_path_buf(),
+                source,
+            })?;
+        }
+        Ok(String::new())
+    } else if diff {
+        let path = file.display().to_string();
+        Ok(printed(
+            TextDiff::from_lines(&src, &migrated)
+                .unified_diff()
+                .header(&path, &path)
+                .to_string(),
+        ))
+    } else {
+        Ok(printed(migrated))
+    }
 }
 
-fn retrieve_description(nix: &rnix::Root, description: &str, category: &str) -> String {
-    if description.is_empty() && category.is_empty() {
-        return String::new();
+fn main_with_options(opts: Options) -> Result<Output, NixdocError> {
+    match &opts.command {
+        Some(Command::JsonSchema { json_version }) => Ok(json_schema(*json_version).into()),
+        Some(Command::Coverage {
+            file,
+            category,
+            follow_imports,
+            json_output,
+            fail_under,
+        }) => report_coverage(file, category, *follow_imports, *json_output, *fail_under),
+        Some(Command::Lint {
+            file,
+            prefix,
+            anchor_prefix,
+            category,
+            follow_imports,
+            deny_warnings,
+        }) => report_lints(
+            file,
+            prefix,
+            anchor_prefix,
+            category,
+            *follow_imports,
+            *deny_warnings,
+        ),
+        Some(Command::ExtractExamples {
+            file,
+            prefix,
+            category,
+            follow_imports,
+            output_dir,
+        }) => extract_examples(
+            file,
+            prefix,
+            category,
+            *follow_imports,
+            output_dir.as_deref(),
+        ),
+        Some(Command::Show {
+            path,
+            file,
+            prefix,
+            category,
+            follow_imports,
+            color,
+        }) => show_entry(path, file, prefix, category, *follow_imports, *color).map(Output::from),
+        Some(Command::Build {
+            config,
+            prefix,
+            anchor_prefix,
+            format,
+            cache_dir,
+            clean,
+            verbose,
+        }) => build(
+            config,
+            Overrides {
+                prefix: prefix.clone(),
+                anchor_prefix: anchor_prefix.clone(),
+                format: *format,
+            },
+            cache_dir.as_deref(),
+            *clean,
+            *verbose,
+        ),
+        Some(Command::Watch {
+            config,
+            prefix,
+            anchor_prefix,
+            format,
+            cache_dir,
+        }) => watch(
+            config,
+            Overrides {
+                prefix: prefix.clone(),
+                anchor_prefix: anchor_prefix.clone(),
+                format: *format,
+            },
+            cache_dir.as_deref(),
+        ),
+        Some(Command::Migrate {
+            file,
+            write,
+            diff,
+            max_depth,
+        }) => migrate_file(file, *write, *diff, *max_depth).map(Output::from),
+        None if opts.follow_imports => render_imports(&opts).map(Output::from),
+        None => render_file(&opts).map(Output::from),
+    }
+}
+
+/// Print the text of an output, and its diagnostics to stderr.
+fn print(output: &Output) {
+    if !output.text.is_empty() {
+        println!("{}", output.text);
+    }
+    for diagnostic in &output.diagnostics {
+        eprintln!("{}", diagnostic);
     }
-    format!(
-        "# {} {{#sec-functions-library-{}}}\n{}\n",
-        description,
-        category,
-        &nix.syntax()
-            .first_child()
-            .and_then(|node| retrieve_doc_comment(&node, Some(1))
-                .or(retrieve_legacy_comment(&node, false)))
-            .and_then(|doc_item| handle_indentation(&doc_item))
-            .unwrap_or_default()
-    )
 }
 
-fn main_with_options(opts: Options) -> String {
-    let src = fs::read_to_string(&opts.file).unwrap();
-    let locs = match opts.locs {
-        None => Default::default(),
-        Some(p) => fs::read_to_string(p)
-            .map_err(|e| e.to_string())
-            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
-            .expect("could not read location information"),
+fn main() {
+    let opts = Options::parse();
+    match main_with_options(opts) {
+        Ok(output) => {
+            print(&output);
+            if let Some(failure) = output.failure {
+                eprintln!("error: {}", failure);
+                process::exit(1);
+            }
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(error.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+    use std::{env, fs, process};
+
+    use nixdoc::{
+        cache::Cache,
+        config::{Overrides, Target},
+        Format, JsonVersion,
     };
-    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
-    let description = retrieve_description(&nix, &opts.description, &opts.category);
 
-    let entries = collect_entries(nix, &opts.prefix, &opts.category, &locs);
+    use crate::{canonical, main_with_options, target_key, Command, Options, Watch};
 
-    if opts.json_output {
-        let json_string = match serde_json::to_string(&JsonFormat {
-            version: 1,
-            entries,
-        }) {
-            Ok(json) => json,
-            Err(error) => panic!("Problem converting entries to JSON: {error:?}"),
+    #[test]
+    fn test_main() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from("strings"),
+            description: String::from("string manipulation functions"),
+            file: Some(PathBuf::from("test/strings.nix")),
+            locs: Some(PathBuf::from("test/strings.json")),
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
         };
-        json_string
-    } else {
-        // TODO: move this to commonmark.rs
-        let mut output = description + "\n";
 
-        for entry in entries {
-            entry.write_section(opts.anchor_prefix.as_str(), &mut output);
-        }
-        output
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
     }
-}
 
-fn main() {
-    let opts = Options::parse();
-    let output = main_with_options(opts);
-    println!("{}", output)
+    #[test]
+    fn test_main_minimal() {
+        let options = Options {
+            command: None,
+            prefix: String::from(""),
+            anchor_prefix: String::from(""),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from(""),
+            description: String::from(""),
+            file: Some(PathBuf::from("test/strings.nix")),
+            locs: Some(PathBuf::from("test/strings.json")),
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_html_output() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::Html,
+            site: None,
+            man_dir: None,
+            category: String::from("strings"),
+            description: String::from("string manipulation functions"),
+            file: Some(PathBuf::from("test/strings.nix")),
+            locs: Some(PathBuf::from("test/strings.json")),
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_man_output() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::Man,
+            site: None,
+            man_dir: None,
+            category: String::from("strings"),
+            description: String::from("string manipulation functions"),
+            file: Some(PathBuf::from("test/strings.nix")),
+            locs: Some(PathBuf::from("test/strings.json")),
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_json_output() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: true,
+            json_version: JsonVersion::V1,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from("strings"),
+            description: String::from("string manipulation functions"),
+            file: Some(PathBuf::from("test/strings.nix")),
+            locs: Some(PathBuf::from("test/strings.json")),
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    // Only version 2 has the fields added since nixdoc 3.x, such as
+    // `sections`, so that version 1 stays the same.
+    #[test]
+    fn test_json_output_v1() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: true,
+            json_version: JsonVersion::V1,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from("nested"),
+            description: String::from("nested attribute sets"),
+            file: Some(PathBuf::from("test/nested-attrsets.nix")),
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_follow_imports() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from(""),
+            description: String::from(""),
+            file: Some(PathBuf::from("test/follow-imports/default.nix")),
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: true,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_source_url_template() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from("inherit"),
+            description: String::from(""),
+            file: Some(PathBuf::from("./test/inherit-from.nix")),
+            locs: None,
+            max_depth: 1,
+            source_url_template: Some(String::from("https://example.com/{path}#L{line}")),
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_json_output_v2() {
+        let options = Options {
+            command: None,
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: true,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::from("sections"),
+            description: String::from(""),
+            file: Some(PathBuf::from("test/doc-comment-sections.nix")),
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        insta::assert_snapshot!(serde_json::to_string_pretty(&output).unwrap());
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let options = Options {
+            command: Some(Command::JsonSchema {
+                json_version: JsonVersion::V2,
+            }),
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::new(),
+            description: String::new(),
+            file: None,
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap().text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_coverage_fail_under() {
+        let options = |fail_under| Options {
+            command: Some(Command::Coverage {
+                file: PathBuf::from("test/coverage.nix"),
+                category: String::from("coverage"),
+                follow_imports: false,
+                json_output: false,
+                fail_under: Some(fail_under),
+            }),
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::new(),
+            description: String::new(),
+            file: None,
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let passed = main_with_options(options(50.0)).unwrap();
+        assert_eq!(passed.failure, None);
+
+        let failed = main_with_options(options(60.0)).unwrap();
+        assert_eq!(failed.text, passed.text);
+        assert_eq!(
+            failed.failure.as_deref(),
+            Some("documentation coverage of 57.14% is below 60%")
+        );
+    }
+
+    #[test]
+    fn test_lint_anchors() {
+        let options = Options {
+            command: Some(Command::Lint {
+                file: PathBuf::from("test/anchors.nix"),
+                prefix: String::from("lib"),
+                anchor_prefix: String::from("function-library-"),
+                category: String::from("anchors"),
+                follow_imports: false,
+                deny_warnings: false,
+            }),
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::new(),
+            description: String::new(),
+            file: None,
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options).unwrap();
+        assert!(output.text.is_empty());
+        assert_eq!(output.diagnostics.len(), 3);
+        assert_eq!(output.failure.as_deref(), Some("2 errors found"));
+    }
+
+    #[test]
+    fn test_lint_cross_category_links() {
+        let options = |file: &str, category: &str, follow_imports| Options {
+            command: Some(Command::Lint {
+                file: PathBuf::from(file),
+                prefix: String::from("lib"),
+                anchor_prefix: String::from("function-library-"),
+                category: String::from(category),
+                follow_imports,
+                deny_warnings: false,
+            }),
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::new(),
+            description: String::new(),
+            file: None,
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        // Links to other categories are not checked for a single file.
+        let output =
+            main_with_options(options("test/cross-category/strings.nix", "strings", false))
+                .unwrap();
+        assert!(output.diagnostics.is_empty());
+
+        // Those of the whole library are checked against all categories.
+        let output =
+            main_with_options(options("test/cross-category/default.nix", "", true)).unwrap();
+        assert_eq!(
+            output.diagnostics,
+            ["test/cross-category/strings.nix:7:3: warning: link to \
+              `#function-library-lib.lists.tail` does not resolve to a known anchor \
+              [broken-link]"]
+        );
+    }
+
+    #[test]
+    fn test_build() {
+        let cache_dir = env::temp_dir().join(format!("nixdoc-test-build-{}", process::id()));
+        let options = |clean| Options {
+            command: Some(Command::Build {
+                config: PathBuf::from("test/nixdoc.toml"),
+                prefix: None,
+                anchor_prefix: None,
+                format: None,
+                cache_dir: Some(cache_dir.clone()),
+                clean,
+                verbose: true,
+            }),
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V2,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::new(),
+            description: String::new(),
+            file: None,
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options(true)).unwrap();
+        assert!(output.diagnostics[2].starts_with("documented 2 files (0 cached)"));
+
+        // The second build takes the documentation from the cache.
+        let cached = main_with_options(options(false)).unwrap();
+        assert!(cached.diagnostics[0].starts_with("test/doc-comment-sections.nix: cached"));
+        assert!(cached.diagnostics[2].starts_with("documented 2 files (2 cached)"));
+        assert_eq!(cached.text, output.text);
+        fs::remove_dir_all(&cache_dir).unwrap();
+
+        let output = output.text;
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_target_key() {
+        let target = Target {
+            path: PathBuf::from("test/doc-comment-sections.nix"),
+            category: String::from("strings"),
+            description: String::new(),
+            prefix: String::from("lib"),
+            anchor_prefix: String::new(),
+            format: Format::CommonMark,
+            max_depth: 1,
+            locs: None,
+            source_url_template: None,
+            output: None,
+        };
+        let key = |target: &Target| target_key(target, b"{ }".to_vec());
+
+        // Every setting rendering the documentation is part of the key.
+        assert_ne!(
+            key(&target),
+            key(&Target {
+                max_depth: 2,
+                ..target.clone()
+            })
+        );
+        assert_ne!(
+            key(&target),
+            key(&Target {
+                locs: Some(PathBuf::from("test/strings.json")),
+                ..target.clone()
+            })
+        );
+        assert_ne!(
+            key(&target),
+            key(&Target {
+                source_url_template: Some(String::from("https://example.com/{path}")),
+                ..target.clone()
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_imports() {
+        let dir = env::temp_dir().join(format!("nixdoc-test-build-imports-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("nixdoc.toml"),
+            "[[file]]\npath = \"default.nix\"\ncategory = \"greetings\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("default.nix"),
+            "{ inherit (import ./helpers.nix { }) greet; }\n",
+        )
+        .unwrap();
+        let helpers = |doc: &str| format!("{{ }}: {{\n  /** {} */\n  greet = x: x;\n}}\n", doc);
+        fs::write(dir.join("helpers.nix"), helpers("Greet someone.")).unwrap();
+
+        let options = |clean| Options {
+            command: Some(Command::Build {
+                config: dir.join("nixdoc.toml"),
+                prefix: None,
+                anchor_prefix: None,
+                format: None,
+                cache_dir: Some(dir.join("cache")),
+                clean,
+                verbose: true,
+            }),
+            prefix: String::from("lib"),
+            anchor_prefix: String::from("function-library-"),
+            json_output: false,
+            json_version: JsonVersion::V1,
+            format: Format::CommonMark,
+            site: None,
+            man_dir: None,
+            category: String::new(),
+            description: String::new(),
+            file: None,
+            locs: None,
+            max_depth: 1,
+            source_url_template: None,
+            follow_imports: false,
+            output_dir: None,
+            cross_links: false,
+            link_index: None,
+            inverted_index: false,
+        };
+
+        let output = main_with_options(options(true)).unwrap();
+        assert!(output.text.contains("Greet someone."));
+        let cached = main_with_options(options(false)).unwrap();
+        assert!(cached.diagnostics[1].starts_with("documented 1 files (1 cached)"));
+
+        // Editing the imported file invalidates the documentation importing it.
+        fs::write(dir.join("helpers.nix"), helpers("Greet someone warmly.")).unwrap();
+        let edited = main_with_options(options(false)).unwrap();
+        assert!(edited.diagnostics[1].starts_with("documented 1 files (0 cached)"));
+        assert!(edited.text.contains("Greet someone warmly."));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_changed() {
+        let cache_dir = env::temp_dir().join(format!("nixdoc-test-watch-{}", process::id()));
+        let mut watch = Watch {
+            config: PathBuf::from("test/nixdoc.toml"),
+            overrides: Overrides::default(),
+            cache: Cache::open(&cache_dir, true).unwrap(),
+            targets: vec![],
+        };
+        watch.load().unwrap();
+        assert!(watch.directories().iter().all(|dir| dir.ends_with("test")));
+
+        // Paths which are not part of the configuration are ignored.
+        let unrelated = HashSet::from([canonical(Path::new("test/strings.nix"))]);
+        assert!(watch.changed(&unrelated).is_none());
+
+        let file = HashSet::from([canonical(Path::new("test/pattern-defaults.nix"))]);
+        let output = watch.changed(&file).unwrap();
+        assert_eq!(output.diagnostics.len(), 1);
+        assert!(output.diagnostics[0].starts_with("test/pattern-defaults.nix: rendered"));
+        assert!(output.text.contains("pkgs.packages.mkPackage"));
+        assert!(!output.text.contains("lib.strings.concatStringsSep"));
+
+        // A changed configuration rebuilds every file.
+        let config = HashSet::from([canonical(Path::new("test/nixdoc.toml"))]);
+        let output = watch.changed(&config).unwrap();
+        assert!(output.diagnostics[0].starts_with("test/doc-comment-sections.nix: rendered"));
+        assert!(output.diagnostics[1].starts_with("test/pattern-defaults.nix: cached"));
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_dependencies() {
+        let dir = env::temp_dir().join(format!("nixdoc-test-watch-imports-{}", process::id()));
+        fs::create_dir_all(dir.join("helpers")).unwrap();
+        fs::write(
+            dir.join("nixdoc.toml"),
+            "[[file]]\npath = \"default.nix\"\ncategory = \"greetings\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("default.nix"),
+            "{ inherit (import ./helpers/greet.nix { }) greet; }\n",
+        )
+        .unwrap();
+        let helpers = |doc: &str| format!("{{ }}: {{\n  /** {} */\n  greet = x: x;\n}}\n", doc);
+        let imported = dir.join("helpers/greet.nix");
+        fs::write(&imported, helpers("Greet someone.")).unwrap();
+
+        let mut watch = Watch {
+            config: dir.join("nixdoc.toml"),
+            overrides: Overrides::default(),
+            cache: Cache::open(&dir.join("cache"), true).unwrap(),
+            targets: vec![],
+        };
+        watch.load().unwrap();
+        assert!(watch
+            .directories()
+            .contains(&canonical(&dir.join("helpers"))));
+
+        // Editing an imported file rebuilds the files importing it.
+        fs::write(&imported, helpers("Greet someone warmly.")).unwrap();
+        let output = watch
+            .changed(&HashSet::from([canonical(&imported)]))
+            .unwrap();
+        assert!(output.diagnostics[0].contains("default.nix: rendered"));
+        assert!(output.text.contains("Greet someone warmly."));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
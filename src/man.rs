@@ -0,0 +1,328 @@
+//! This module implements the man page output: rendering the entries of a
+//! category, or each entry on its own, as roff pages of section 3.
+//!
+//! Descriptions are CommonMark, which is translated to the `man` macros:
+//! paragraphs, lists, definition lists, code blocks, inline code and
+//! emphasis. Fenced divs such as `::: {.example}` are dropped, their
+//! content is rendered as is.
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use crate::{
+    commonmark::{get_title, Argument, ManualEntry},
+    format::{fenced_div, CodeFences},
+};
+
+/// Render a category as a single man page, e.g. `lib.strings(3)`.
+///
+/// `description` is the rendered heading and description of the category
+/// (see [crate::retrieve_description]); the heading becomes the summary
+/// of the page. Each entry is rendered as a section of the page.
+pub fn render_man(name: &str, description: &str, entries: Vec<ManualEntry>) -> String {
+    let (title, body) = match description.split_once('\n') {
+        Some((heading, body)) if heading.starts_with("# ") => (heading_text(heading), body),
+        _ => ("", description),
+    };
+
+    let mut roff = Roff::default();
+    roff.title(name);
+    roff.heading(".SH", "NAME");
+    roff.summary(name, title);
+    if !body.trim().is_empty() {
+        roff.heading(".SH", "DESCRIPTION");
+        roff.markdown(body);
+    }
+    for entry in &entries {
+        let (_, title) = entry.get_ident_title();
+        roff.heading(".SH", &title);
+        roff.entry(entry, ".SS");
+    }
+    roff.output
+}
+
+/// Render a man page per entry, e.g. `lib.strings.concatStrings(3)`,
+/// returning the pages by their file name (e.g.
+/// `lib.strings.concatStrings.3`).
+///
+/// Pages refer to the page of their category, see [render_man].
+pub fn render_man_pages(entries: &[ManualEntry]) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .map(|entry| {
+            let (_, title) = entry.get_ident_title();
+            let mut roff = Roff::default();
+            roff.title(&title);
+            roff.heading(".SH", "NAME");
+            roff.summary(&title, &summary(&entry.description));
+            roff.entry(entry, ".SH");
+            if !entry.category.is_empty() {
+                roff.heading(".SH", "SEE ALSO");
+                roff.raw("\\fB");
+                roff.text(&get_title(&entry.prefix, &entry.category, ""));
+                roff.raw("\\fR(3)\n");
+            }
+            (format!("{}.3", title), roff.output)
+        })
+        .collect()
+}
+
+/// Returns the text of a Markdown heading line, without its hashes and
+/// attributes, e.g. `Strings` of `# Strings {#sec-strings}`.
+fn heading_text(heading: &str) -> &str {
+    let text = heading.trim_start_matches('#').trim();
+    match text.rfind('{') {
+        Some(start) if text.ends_with('}') => text[..start].trim_end(),
+        _ => text,
+    }
+}
+
+/// Returns the first sentence of a description, for the NAME section.
+fn summary(description: &[String]) -> String {
+    let paragraph = description.first().map_or("", String::as_str);
+    let text = paragraph
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('`', "");
+    match text.find(". ") {
+        Some(end) => text[..=end].to_string(),
+        None => text,
+    }
+}
+
+/// A roff document being written.
+#[derive(Default)]
+struct Roff {
+    output: String,
+}
+
+impl Roff {
+    /// Write the title line of a page of section 3.
+    fn title(&mut self, name: &str) {
+        self.request(&format!(
+            ".TH {} 3 \"\" nixdoc",
+            quote(&name.to_uppercase())
+        ));
+    }
+
+    /// Write a heading, e.g. `.SH "SEE ALSO"`.
+    fn heading(&mut self, request: &str, text: &str) {
+        self.request(&format!("{} {}", request, quote(text)));
+    }
+
+    /// Write the line of a NAME section, e.g. `lib.strings \- Strings`.
+    fn summary(&mut self, name: &str, summary: &str) {
+        self.text(name);
+        if !summary.is_empty() {
+            self.raw(" \\- ");
+            self.text(summary);
+        }
+        self.raw("\n");
+    }
+
+    /// Write the sections of an entry, using `heading` (`.SH` or `.SS`) as
+    /// the request of their headings.
+    fn entry(&mut self, entry: &ManualEntry, heading: &str) {
+        if let Some(fn_type) = &entry.fn_type {
+            self.heading(heading, "SYNOPSIS");
+            self.request(".nf");
+            self.text(fn_type.trim());
+            self.request(".fi");
+        }
+
+        let prose = entry.prose();
+        if !prose.trim().is_empty() {
+            self.heading(heading, "DESCRIPTION");
+            self.markdown(&prose);
+        }
+
+        if entry.lists_args() {
+            self.heading(heading, "ARGUMENTS");
+            let arguments: String = entry
+                .rendered_args()
+                .into_iter()
+                .map(Argument::format_argument)
+                .collect();
+            self.markdown(&arguments);
+        }
+
+        let examples = entry.example_blocks();
+        if !examples.is_empty() {
+            self.heading(heading, "EXAMPLES");
+            for example in examples {
+                self.code_block(example.code.trim());
+            }
+        }
+
+        for (title, section) in &entry.sections {
+            self.heading(heading, &title.to_uppercase());
+            self.markdown(section);
+        }
+    }
+
+    /// Write an indented code block, which is not filled.
+    fn code_block(&mut self, code: &str) {
+        self.request(".PP");
+        self.request(".RS 4");
+        self.request(".nf");
+        self.text(code.trim_end_matches('\n'));
+        self.request(".fi");
+        self.request(".RE");
+    }
+
+    /// Write CommonMark.
+    fn markdown(&mut self, markdown: &str) {
+        let mut fences = CodeFences::default();
+        let markdown: String = markdown
+            .lines()
+            .filter(|line| fences.update(line) || fenced_div(line).is_none())
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        // Lists being written, with the next number of ordered lists.
+        let mut lists: Vec<Option<u64>> = vec![];
+        // Whether the next paragraph continues the tag of an item.
+        let mut item_start = false;
+        let mut links = vec![];
+
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_HEADING_ATTRIBUTES
+            | Options::ENABLE_DEFINITION_LIST;
+        for event in Parser::new_ext(&markdown, options) {
+            let nested = !lists.is_empty();
+            match event {
+                Event::Start(Tag::Paragraph) if item_start => item_start = false,
+                Event::Start(Tag::Paragraph) => self.request(if nested { ".IP" } else { ".PP" }),
+                Event::Start(Tag::Heading { .. }) => self.raw_line(".SS "),
+                Event::Start(Tag::CodeBlock(_)) => {
+                    self.request(if nested && !item_start { ".IP" } else { ".PP" });
+                    item_start = false;
+                    self.request(".RS 4");
+                    self.request(".nf");
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    self.request(".fi");
+                    self.request(".RE");
+                }
+                Event::Start(Tag::BlockQuote(_)) => self.request(".RS 4"),
+                Event::End(TagEnd::BlockQuote(_)) => self.request(".RE"),
+                Event::Start(Tag::List(start)) => {
+                    if !lists.is_empty() {
+                        self.request(".RS 4");
+                    }
+                    lists.push(start);
+                }
+                Event::Start(Tag::DefinitionList) => {
+                    if !lists.is_empty() {
+                        self.request(".RS 4");
+                    }
+                    lists.push(None);
+                }
+                Event::End(TagEnd::List(_) | TagEnd::DefinitionList) => {
+                    lists.pop();
+                    if !lists.is_empty() {
+                        self.request(".RE");
+                    }
+                }
+                Event::Start(Tag::Item) => {
+                    let marker = match lists.last_mut() {
+                        Some(Some(number)) => {
+                            *number += 1;
+                            format!("{}.", *number - 1)
+                        }
+                        _ => "\\(bu".to_string(),
+                    };
+                    self.request(&format!(".IP {} 4", marker));
+                    item_start = true;
+                }
+                Event::Start(Tag::DefinitionListTitle) => self.request(".TP"),
+                Event::Start(Tag::DefinitionListDefinition) => item_start = true,
+                Event::End(
+                    TagEnd::Paragraph
+                    | TagEnd::Heading(_)
+                    | TagEnd::Item
+                    | TagEnd::DefinitionListTitle
+                    | TagEnd::DefinitionListDefinition
+                    | TagEnd::TableHead
+                    | TagEnd::TableRow,
+                ) => self.end_line(),
+                Event::Start(Tag::Emphasis) => self.raw("\\fI"),
+                Event::Start(Tag::Strong) => self.raw("\\fB"),
+                Event::End(TagEnd::Emphasis | TagEnd::Strong) => self.raw("\\fR"),
+                Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    ..
+                }) => links.push((link_type, dest_url)),
+                Event::End(TagEnd::Link) => {
+                    // Links to anchors of the documentation have no target
+                    // in a man page, their text is enough.
+                    if let Some((link_type, dest_url)) = links.pop() {
+                        if link_type != LinkType::Autolink && !dest_url.starts_with('#') {
+                            self.text(&format!(" <{}>", dest_url));
+                        }
+                    }
+                }
+                Event::Start(Tag::TableCell) => self.raw(" "),
+                Event::Text(text) => self.text(&text),
+                Event::Code(code) => {
+                    self.raw("\\fB");
+                    self.text(&code);
+                    self.raw("\\fR");
+                }
+                Event::SoftBreak => self.raw("\n"),
+                Event::HardBreak => self.request(".br"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Write a request on a line of its own, e.g. `.PP`.
+    fn request(&mut self, request: &str) {
+        self.end_line();
+        self.output.push_str(request);
+        self.output.push('\n');
+    }
+
+    /// Start a line with a request taking the text that follows, e.g.
+    /// `.SS ` for a heading.
+    fn raw_line(&mut self, request: &str) {
+        self.end_line();
+        self.output.push_str(request);
+    }
+
+    /// Terminate the current line, if it is not empty.
+    fn end_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+    }
+
+    /// Write roff as is.
+    fn raw(&mut self, roff: &str) {
+        self.output.push_str(roff);
+    }
+
+    /// Write text, escaping backslashes, dashes, and dots and apostrophes
+    /// at the start of lines, which would be read as requests.
+    fn text(&mut self, text: &str) {
+        for c in text.chars() {
+            let line_start = self.output.is_empty() || self.output.ends_with('\n');
+            match c {
+                '.' | '\'' if line_start => self.output.push_str("\\&"),
+                _ => {}
+            }
+            match c {
+                '\\' => self.output.push_str("\\e"),
+                '-' => self.output.push_str("\\-"),
+                _ => self.output.push(c),
+            }
+        }
+    }
+}
+
+/// Quote an argument of a request, e.g. a heading containing spaces.
+fn quote(argument: &str) -> String {
+    format!("\"{}\"", argument.replace('"', "\\(dq"))
+}
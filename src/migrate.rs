@@ -0,0 +1,223 @@
+//! This module implements migrating legacy doc comments to RFC145 doc
+//! comments, as described in `doc/migration.md`.
+//!
+//! A legacy comment such as
+//!
+//! ```nix
+//! /* Filter an attribute set.
+//!
+//!    Example:
+//!      filterAttrs (n: v: n == "foo") { foo = 1; bar = 2; }
+//!      => { foo = 1; }
+//!
+//!    Type: filterAttrs :: (String -> Any -> Bool) -> AttrSet -> AttrSet
+//! */
+//! filterAttrs =
+//!   # Predicate taking an attribute name and an attribute value
+//!   pred:
+//!   # The attribute set to filter
+//!   set:
+//!   ...
+//! ```
+//!
+//! is rewritten into a `/** ... */` doc comment with `# Example`, `# Type`
+//! and `# Arguments` sections, and the comments of the curried arguments
+//! are removed. Comments of the entries of a pattern are turned into doc
+//! comments in place. Everything else is preserved byte for byte.
+
+use rnix::{
+    ast::{AttrpathValue, Expr, Lambda, Param},
+    SyntaxKind, SyntaxToken, TextRange,
+};
+use rowan::ast::AstNode;
+
+use crate::{
+    collected_bindings,
+    format::handle_indentation,
+    legacy::{legacy_comment_tokens, retrieve_legacy_comment},
+    parse_doc_comment, retrieve_doc_comment,
+};
+
+/// A replacement of a range of the source.
+struct Edit {
+    range: TextRange,
+    text: String,
+}
+
+/// Migrate the legacy doc comments of the bindings documented by nixdoc in
+/// a parsed Nix file, returning the migrated source.
+///
+/// These are the bindings of the top-level attribute set and its nested
+/// attribute sets until `max_depth`, and of a top-level `let` expression,
+/// which are documented if they are inherited into the attribute set.
+/// Comments of other bindings, e.g. within function bodies, and bindings
+/// which already have a doc comment are left as they are.
+pub fn migrate(root: &rnix::Root, max_depth: usize) -> String {
+    let src = root.syntax().to_string();
+    let mut edits = vec![];
+
+    for apv in collected_bindings(root, max_depth) {
+        if retrieve_doc_comment(apv.syntax(), None).is_none() {
+            edits.extend(migrate_binding(&src, &apv));
+        }
+    }
+
+    // Edits are applied back to front, so their ranges remain valid.
+    edits.sort_by_key(|edit| edit.range.start());
+    let mut result = src.clone();
+    for edit in edits.into_iter().rev() {
+        result.replace_range(std::ops::Range::<usize>::from(edit.range), &edit.text);
+    }
+    result
+}
+
+/// Returns the edits migrating the legacy doc comment of a binding, if it
+/// has one.
+fn migrate_binding(src: &str, apv: &AttrpathValue) -> Vec<Edit> {
+    let Some(tokens) = legacy_comment_tokens(apv.syntax(), false) else {
+        return vec![];
+    };
+    let Some(comment) = retrieve_legacy_comment(apv.syntax(), false) else {
+        return vec![];
+    };
+    let comment = parse_doc_comment(&comment);
+
+    let mut edits = vec![];
+    let mut args = vec![];
+    if let Some(Expr::Lambda(lambda)) = apv.value() {
+        migrate_arguments(src, lambda, &mut args, &mut edits);
+    }
+
+    let mut sections = vec![];
+    if !comment.doc.is_empty() {
+        sections.push(comment.doc);
+    }
+    if let Some(example) = comment.example {
+        sections.push(format!("# Example\n\n```nix\n{}\n```", example));
+    }
+    if let Some(fn_type) = comment.doc_type {
+        sections.push(format!("# Type\n\n```\n{}\n```", fn_type));
+    }
+    if !args.is_empty() {
+        let definitions: Vec<String> = args
+            .iter()
+            .map(|(name, doc)| format!("`{}`\n: {}", name, indent(doc, "  ")))
+            .collect();
+        sections.push(format!("# Arguments\n\n{}", definitions.join("\n\n")));
+    }
+    if sections.is_empty() {
+        return edits;
+    }
+
+    let first = &tokens[0];
+    let range = TextRange::new(
+        first.text_range().start(),
+        tokens[tokens.len() - 1].text_range().end(),
+    );
+    let indentation = line_indentation(src, first);
+    let content = indent(&sections.join("\n\n"), &format!("{}  ", indentation));
+    edits.push(Edit {
+        range,
+        text: format!("/**\n{}  {}\n{}*/", indentation, content, indentation),
+    });
+    edits
+}
+
+/// Collect the documented arguments of curried lambdas, removing their
+/// comments, and turn the comments of pattern entries into doc comments.
+fn migrate_arguments(
+    src: &str,
+    mut lambda: Lambda,
+    args: &mut Vec<(String, String)>,
+    edits: &mut Vec<Edit>,
+) {
+    loop {
+        match lambda.param() {
+            Some(Param::IdentParam(id)) => {
+                let doc = retrieve_legacy_comment(id.syntax(), true)
+                    .and_then(|comment| handle_indentation(&comment));
+                let tokens = legacy_comment_tokens(id.syntax(), true);
+                if let (Some(doc), Some(tokens)) = (doc, tokens) {
+                    args.push((id.to_string(), doc));
+                    edits.push(remove_comment(&tokens));
+                }
+            }
+            Some(Param::Pattern(pattern)) => {
+                for entry in pattern.pat_entries() {
+                    if retrieve_doc_comment(entry.syntax(), None).is_some() {
+                        continue;
+                    }
+                    let doc = retrieve_legacy_comment(entry.syntax(), true)
+                        .and_then(|comment| handle_indentation(&comment));
+                    let tokens = legacy_comment_tokens(entry.syntax(), true);
+                    if let (Some(doc), Some(tokens)) = (doc, tokens) {
+                        let first = &tokens[0];
+                        let range = TextRange::new(
+                            first.text_range().start(),
+                            tokens[tokens.len() - 1].text_range().end(),
+                        );
+                        let indentation = line_indentation(src, first);
+                        edits.push(Edit {
+                            range,
+                            text: match doc.contains('\n') {
+                                true => format!(
+                                    "/**\n{}  {}\n{}*/",
+                                    indentation,
+                                    indent(&doc, &format!("{}  ", indentation)),
+                                    indentation
+                                ),
+                                false => format!("/** {} */", doc),
+                            },
+                        });
+                    }
+                }
+            }
+            None => break,
+        }
+
+        match lambda.body() {
+            Some(Expr::Lambda(inner)) => lambda = inner,
+            _ => break,
+        }
+    }
+}
+
+/// Returns an edit removing comment tokens, together with the whitespace
+/// following them, e.g. `# The predicate\n  ` before `pred:`.
+fn remove_comment(tokens: &[SyntaxToken]) -> Edit {
+    let last = &tokens[tokens.len() - 1];
+    let end = match last.next_token() {
+        Some(next) if next.kind() == SyntaxKind::TOKEN_WHITESPACE => next.text_range().end(),
+        _ => last.text_range().end(),
+    };
+    Edit {
+        range: TextRange::new(tokens[0].text_range().start(), end),
+        text: String::new(),
+    }
+}
+
+/// Returns the indentation of the line a token starts on, i.e. the
+/// whitespace before it, or as many spaces if it is preceded by code.
+fn line_indentation(src: &str, token: &SyntaxToken) -> String {
+    let start = usize::from(token.text_range().start());
+    let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    let prefix = &src[line_start..start];
+    if prefix.trim().is_empty() {
+        prefix.to_string()
+    } else {
+        " ".repeat(prefix.chars().count())
+    }
+}
+
+/// Indent all lines but the first one, leaving empty lines empty.
+fn indent(text: &str, indentation: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| match index {
+            0 => line.to_string(),
+            _ if line.trim().is_empty() => String::new(),
+            _ => format!("{}{}", indentation, line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
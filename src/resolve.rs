@@ -0,0 +1,248 @@
+//! This module implements static resolution of the sources of
+//! `inherit (source) names;` clauses.
+//!
+//! nixdoc does not evaluate Nix, but the sources commonly used to
+//! re-export library functions can be resolved syntactically:
+//!
+//! * literal attribute sets, e.g. `inherit ({ a = 1; }) a;`
+//! * let-bound attribute sets, e.g. `inherit (helpers) a;`
+//! * attribute selections of those, e.g. `inherit (lib.strings) a;`
+//! * imports of relative paths, e.g. `inherit (import ./helpers.nix { }) a;`
+
+use rnix::ast::{Apply, AttrSet, AttrpathValue, Expr, HasEntry};
+use rnix::SyntaxNode;
+use rowan::ast::AstNode;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::location::{LineIndex, SourceLocation};
+
+/// Limit for the number of indirections followed while resolving an
+/// expression. This protects against cyclic bindings such as `a = a;`
+/// or files importing themselves.
+const MAX_INDIRECTIONS: usize = 32;
+
+/// The let-bindings visible to an expression, together with the file
+/// it is defined in. Relative paths are resolved against that file.
+#[derive(Clone, Debug, Default)]
+pub struct Scope {
+    bindings: HashMap<String, Expr>,
+    file: Option<PathBuf>,
+
+    /// The line index of the source of the file, if its expressions are
+    /// located, see [Scope::locate]. Imported files are indexed too then.
+    lines: Option<Arc<LineIndex>>,
+}
+
+impl Scope {
+    /// Create an empty scope for expressions in `file`, which are located
+    /// by `lines`, if given.
+    pub fn new(file: Option<&Path>, lines: Option<Arc<LineIndex>>) -> Self {
+        Scope {
+            bindings: HashMap::new(),
+            file: file.map(Path::to_path_buf),
+            lines,
+        }
+    }
+
+    /// The file expressions in this scope are defined in.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Locate a node of the file of this scope, unless it has no line index.
+    pub fn locate(&self, node: &SyntaxNode) -> Option<SourceLocation> {
+        Some(self.lines.as_ref()?.locate(node, self.file.clone()))
+    }
+
+    /// Returns this scope extended by the bindings of a `let ... in`
+    /// expression or a (recursive) attribute set.
+    pub fn with_bindings(&self, node: &impl HasEntry) -> Self {
+        let mut scope = self.clone();
+        for apv in node.attrpath_values() {
+            if let (Some(name), Some(value)) = (single_attr_name(&apv), apv.value()) {
+                scope.bindings.insert(name, value);
+            }
+        }
+        scope
+    }
+}
+
+/// Resolve `expr` to the attribute set it statically evaluates to.
+///
+/// Returns the attribute set and the scope its values are defined in, or
+/// `None` if the expression cannot be resolved without evaluating it.
+pub fn resolve_attrset(expr: Expr, scope: &Scope) -> Option<(AttrSet, Scope)> {
+    resolve(expr, scope, 0)
+}
+
+fn resolve(expr: Expr, scope: &Scope, indirections: usize) -> Option<(AttrSet, Scope)> {
+    if indirections > MAX_INDIRECTIONS {
+        return None;
+    }
+    let indirections = indirections + 1;
+
+    match expr {
+        Expr::AttrSet(set) => {
+            let scope = if set.rec_token().is_some() {
+                scope.with_bindings(&set)
+            } else {
+                scope.clone()
+            };
+            Some((set, scope))
+        }
+        Expr::Paren(paren) => resolve(paren.expr()?, scope, indirections),
+        Expr::Ident(ident) => {
+            let name = ident.ident_token()?.text().to_string();
+            resolve(scope.bindings.get(&name)?.clone(), scope, indirections)
+        }
+        Expr::LetIn(let_in) => resolve(let_in.body()?, &scope.with_bindings(&let_in), indirections),
+        Expr::With(with) => resolve(with.body()?, scope, indirections),
+        // Library files are usually functions taking `{ lib, ... }`.
+        // Arguments are not evaluated, only the returned set is of interest.
+        Expr::Lambda(lambda) => resolve(lambda.body()?, scope, indirections),
+        Expr::Select(select) => {
+            // `x.y or default` cannot be decided statically.
+            if select.default_expr().is_some() {
+                return None;
+            }
+            let (mut set, mut scope) = resolve(select.expr()?, scope, indirections)?;
+            for attr in select.attrpath()?.attrs() {
+                let name = attr.syntax().text().to_string();
+                let value = lookup(&set, &scope, &name, indirections)?.value()?;
+                (set, scope) = resolve(value, &scope, indirections)?;
+            }
+            Some((set, scope))
+        }
+        Expr::Apply(apply) => {
+            let file = import_path(&apply, scope)?;
+            let src = fs::read_to_string(&file).ok()?;
+            let root = rnix::Root::parse(&src).tree();
+            let lines = scope.lines.as_ref().map(|_| Arc::new(LineIndex::new(&src)));
+            resolve(root.expr()?, &Scope::new(Some(&file), lines), indirections)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the file imported by an application of `import` to a relative
+/// path, e.g. `import ./strings.nix` or `import ./strings.nix { inherit lib; }`.
+///
+/// `callLibs ./strings.nix` is treated like an import as well. This is the
+/// helper nixpkgs' `lib/default.nix` uses to import a file with `lib` as
+/// its argument.
+///
+/// Importing a directory imports its `default.nix`.
+pub fn import_path(apply: &Apply, scope: &Scope) -> Option<PathBuf> {
+    match apply.lambda()? {
+        Expr::Ident(ident)
+            if matches!(
+                ident.syntax().text().to_string().as_str(),
+                "import" | "callLibs"
+            ) =>
+        {
+            match apply.argument()? {
+                Expr::Path(path) => {
+                    let path = path.syntax().text().to_string();
+                    if !(path.starts_with("./") || path.starts_with("../")) {
+                        return None;
+                    }
+                    let file: PathBuf = scope
+                        .file()?
+                        .parent()?
+                        .join(path)
+                        .components()
+                        .filter(|component| *component != Component::CurDir)
+                        .collect();
+                    if file.is_dir() {
+                        Some(file.join("default.nix"))
+                    } else {
+                        Some(file)
+                    }
+                }
+                _ => None,
+            }
+        }
+        // The imported file is applied to arguments, which don't change
+        // the attribute names it exports.
+        Expr::Apply(inner) => import_path(&inner, scope),
+        _ => None,
+    }
+}
+
+/// Returns the files the resolution of the `inherit (source)` clauses of
+/// `file` may read: the files it imports by relative paths, see
+/// [import_path], and the files those import in turn. `file` itself is not
+/// included, but imported files which do not exist are, so that creating
+/// them can be noticed too.
+pub fn dependencies(file: &Path) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    let mut pending = vec![file.to_path_buf()];
+    while let Some(file) = pending.pop() {
+        let Ok(src) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let scope = Scope::new(Some(&file), None);
+        let root = rnix::Root::parse(&src).tree();
+        for apply in root.syntax().descendants().filter_map(Apply::cast) {
+            if let Some(import) = import_path(&apply, &scope) {
+                if files.insert(import.clone()) {
+                    pending.push(import);
+                }
+            }
+        }
+    }
+    files.remove(file);
+    files
+}
+
+/// Find the definition of `name` in an attribute set, i.e. the binding
+/// `name = value;`. Definitions brought into the set by `inherit name;`
+/// or `inherit (source) name;` are followed to their original binding.
+///
+/// `scope` is the scope the attribute set is defined in, as returned by
+/// [resolve_attrset].
+pub fn find_definition(set: &AttrSet, scope: &Scope, name: &str) -> Option<AttrpathValue> {
+    lookup(set, scope, name, 0)
+}
+
+fn lookup(set: &AttrSet, scope: &Scope, name: &str, indirections: usize) -> Option<AttrpathValue> {
+    if indirections > MAX_INDIRECTIONS {
+        return None;
+    }
+
+    if let Some(apv) = set
+        .attrpath_values()
+        .find(|apv| single_attr_name(apv).as_deref() == Some(name))
+    {
+        return Some(apv);
+    }
+
+    let inherit = set
+        .inherits()
+        .find(|inh| inh.attrs().any(|attr| attr.syntax().text() == name))?;
+    match inherit.from() {
+        // The value of a let-binding is the child of its `AttrpathValue`.
+        None => scope
+            .bindings
+            .get(name)?
+            .syntax()
+            .parent()
+            .and_then(AttrpathValue::cast),
+        Some(from) => {
+            let (source, scope) = resolve(from.expr()?, scope, indirections + 1)?;
+            lookup(&source, &scope, name, indirections + 1)
+        }
+    }
+}
+
+/// Returns the name of a binding whose attrpath consists of a single attr.
+fn single_attr_name(apv: &AttrpathValue) -> Option<String> {
+    let mut attrs = apv.attrpath()?.attrs();
+    match (attrs.next(), attrs.next()) {
+        (Some(attr), None) => Some(attr.syntax().text().to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,155 @@
+//! This module implements the search index output, a compact JSON
+//! document for searching the documentation on the client side, e.g. by
+//! a small script of a static site.
+//!
+//! Every entry is listed with its title, anchor, type signature, the
+//! first paragraph of its description and the tokens of its name.
+//! Optionally, an inverted index maps every token of the names, type
+//! signatures and whole descriptions to the entries containing it, so that
+//! clients do not have to build it when the page is loaded.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{commonmark::ManualEntry, NixdocError};
+
+/// Words of descriptions which are too common to be worth indexing.
+const STOP_WORDS: &[&str] = &[
+    "an", "and", "are", "as", "at", "be", "by", "for", "from", "if", "in", "is", "it", "of", "on",
+    "or", "the", "this", "to", "with",
+];
+
+#[derive(Serialize)]
+struct SearchIndex {
+    version: u32,
+    entries: Vec<SearchEntry>,
+
+    /// Maps tokens to the positions of the entries containing them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<BTreeMap<String, Vec<usize>>>,
+}
+
+#[derive(Serialize)]
+struct SearchEntry {
+    /// The title of the entry, e.g. `lib.strings.concatStrings`.
+    title: String,
+
+    /// The anchor of the entry in the documentation, without `#`.
+    anchor: String,
+
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    fn_type: Option<String>,
+
+    /// The first paragraph of the description.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+
+    /// The tokens of the title, see [tokenize].
+    tokens: Vec<String>,
+}
+
+/// Render a search index of the given entries, with anchors as in
+/// [crate::render_commonmark].
+///
+/// With `inverted_index`, the index also maps tokens to the positions of
+/// the entries containing them.
+pub fn render_search_index(
+    entries: &[ManualEntry],
+    anchor_prefix: &str,
+    inverted_index: bool,
+) -> Result<String, NixdocError> {
+    let mut index: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let entries = entries
+        .iter()
+        .enumerate()
+        .map(|(position, entry)| {
+            let (ident, title) = entry.get_ident_title();
+            let fn_type = entry.fn_type.as_deref().map(collapse_whitespace);
+            let prose = entry.prose();
+            let description = summary(&prose);
+
+            let mut tokens = tokenize(&title);
+            tokens.extend(tokenize(fn_type.as_deref().unwrap_or_default()));
+            tokens.extend(
+                tokenize(&prose)
+                    .into_iter()
+                    .filter(|token| !STOP_WORDS.contains(&token.as_str())),
+            );
+            for token in tokens {
+                let positions = index.entry(token).or_default();
+                if positions.last() != Some(&position) {
+                    positions.push(position);
+                }
+            }
+
+            SearchEntry {
+                tokens: tokenize(&title),
+                title,
+                anchor: format!("{}{}", anchor_prefix, ident),
+                fn_type,
+                description,
+            }
+        })
+        .collect();
+
+    serde_json::to_string(&SearchIndex {
+        version: 1,
+        entries,
+        index: inverted_index.then_some(index),
+    })
+    .map_err(NixdocError::Json)
+}
+
+/// Returns the first paragraph of a description on a single line, without
+/// the backticks of inline code.
+fn summary(description: &str) -> String {
+    let paragraph = description.trim().split("\n\n").next().unwrap_or_default();
+    collapse_whitespace(&paragraph.replace('`', ""))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split text into lowercase tokens for searching.
+///
+/// Every word is a token, and so is every part of a word in camel case,
+/// e.g. `concatmapstrings`, `concat`, `map` and `strings` of
+/// `concatMapStrings`. Tokens of a single character are left out.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let parts = camel_case_parts(word);
+        let words = std::iter::once(word).chain(parts.iter().copied().filter(|_| parts.len() > 1));
+        for token in words.map(str::to_lowercase) {
+            if token.chars().count() > 1 && !tokens.contains(&token) {
+                tokens.push(token);
+            }
+        }
+    }
+    tokens
+}
+
+/// Split a word in camel case into its parts, e.g. `to` and `JSON` of
+/// `toJSON`, or `attrs` and `2` of `attrs2`.
+fn camel_case_parts(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = vec![];
+    let mut start = 0;
+    for (i, &(offset, c)) in chars.iter().enumerate().skip(1) {
+        let previous = chars[i - 1].1;
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let boundary = (c.is_uppercase() && !previous.is_uppercase())
+            // The last capital of an acronym starts the next part, e.g. of `JSONValue`.
+            || (c.is_uppercase() && previous.is_uppercase() && next.is_some_and(char::is_lowercase))
+            || (c.is_ascii_digit() != previous.is_ascii_digit());
+        if boundary {
+            parts.push(&word[start..offset]);
+            start = offset;
+        }
+    }
+    if start < word.len() {
+        parts.push(&word[start..]);
+    }
+    parts
+}
@@ -0,0 +1,210 @@
+//! This module implements parsing of the headed sections of RFC145 doc
+//! comments, such as `# Type`, `# Arguments` and `# Examples`.
+//!
+//! The sections are only parsed to provide structured data. Markdown
+//! output renders RFC145 doc comments verbatim.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::{
+    commonmark::{Argument, SingleArg},
+    format::{handle_indentation, trim_leading_whitespace, CodeFences},
+};
+
+/// A fenced code block, e.g. a usage example.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CodeBlock {
+    /// The info string of the opening fence (e.g. 'nix'), if any.
+    pub info: Option<String>,
+
+    /// Content of the code block.
+    pub code: String,
+}
+
+/// The sections of a doc comment.
+#[derive(Debug, Default)]
+pub struct DocSections {
+    /// Content of the `# Type` section. If the section contains a code
+    /// block, this is the content of the code block.
+    pub fn_type: Option<String>,
+
+    /// Definitions of the `# Arguments` (or `# Inputs`) definition list.
+    pub args: Vec<Argument>,
+
+    /// Code blocks of the `# Examples` (or `# Example`) section.
+    pub examples: Vec<CodeBlock>,
+
+    /// All other sections, keyed by their heading.
+    pub other: BTreeMap<String, String>,
+}
+
+/// Split a doc comment into its sections.
+///
+/// Sections are started by the headings of the highest level present in
+/// the doc comment, usually H1. The text before the first section is the
+/// description and not part of any section.
+pub fn parse_sections(doc: &str) -> DocSections {
+    let mut headings = vec![];
+    let mut fences = CodeFences::default();
+    for (index, line) in doc.lines().enumerate() {
+        if fences.update(line) {
+            continue;
+        }
+        if let Some((level, title)) = parse_heading(line) {
+            headings.push((index, level, title));
+        }
+    }
+
+    let mut result = DocSections::default();
+    let Some(section_level) = headings.iter().map(|(_, level, _)| *level).min() else {
+        return result;
+    };
+    let headings: Vec<_> = headings
+        .into_iter()
+        .filter(|(_, level, _)| *level == section_level)
+        .collect();
+
+    let lines: Vec<&str> = doc.lines().collect();
+    for (i, (start, _, title)) in headings.iter().enumerate() {
+        let end = headings.get(i + 1).map_or(lines.len(), |(end, _, _)| *end);
+        let content = lines[start + 1..end].join("\n");
+
+        match title.to_lowercase().as_str() {
+            "type" => {
+                result.fn_type = code_blocks(&content)
+                    .into_iter()
+                    .next()
+                    .map(|block| block.code)
+                    .or_else(|| handle_indentation(&content));
+            }
+            "arguments" | "inputs" => result.args.extend(definitions(&content)),
+            "example" | "examples" => result.examples.extend(code_blocks(&content)),
+            _ => {
+                if let Some(content) = handle_indentation(&content) {
+                    result.other.insert(title.clone(), content);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Parse an ATX heading, returning its level and title.
+///
+/// A trailing sequence of `#`s and an attribute block such as
+/// `{#anchor}` are not part of the title.
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+    // 4 or more spaces of indentation make this an indented code block.
+    let line = trim_leading_whitespace(line, 3);
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if level == 0 || level > 6 || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    let mut title = rest.trim();
+    if title.ends_with('}') {
+        if let Some(start) = title.rfind('{') {
+            title = title[..start].trim_end();
+        }
+    }
+    let title = title.trim_end_matches('#').trim_end();
+    Some((level, title.to_string()))
+}
+
+/// Extract the fenced code blocks of a piece of Markdown.
+pub(crate) fn code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let mut blocks = vec![];
+    let mut fences = CodeFences::default();
+    let mut current: Option<(Option<String>, Vec<&str>)> = None;
+
+    for line in markdown.lines() {
+        fences.update(line);
+        match current.as_mut() {
+            // Start of a code block
+            None if fences.in_code_block() => {
+                let info = trim_leading_whitespace(line, 3)
+                    .trim_start_matches(['`', '~'])
+                    .trim()
+                    .to_string();
+                current = Some((Some(info).filter(|info| !info.is_empty()), vec![]));
+            }
+            None => (),
+            // Content of the code block
+            Some((_, code)) if fences.in_code_block() => code.push(line),
+            // End of the code block
+            Some(_) => {
+                let (info, code) = current.take().unwrap();
+                blocks.push(CodeBlock {
+                    info,
+                    code: code.join("\n"),
+                });
+            }
+        }
+    }
+
+    // As in CommonMark, a code block which is not closed ends with the Markdown.
+    if let Some((info, code)) = current {
+        blocks.push(CodeBlock {
+            info,
+            code: code.join("\n"),
+        });
+    }
+
+    blocks
+}
+
+/// Parse the definition list of an arguments section, e.g.
+///
+/// ```markdown
+/// `a`
+/// : The first number
+///
+/// b
+/// : The second number
+///   which spans multiple lines
+/// ```
+fn definitions(markdown: &str) -> Vec<Argument> {
+    let mut args: Vec<(String, Vec<&str>)> = vec![];
+    let mut term: Option<&str> = None;
+    let mut in_definition = false;
+
+    for line in markdown.lines() {
+        if let Some(definition) = line.strip_prefix(':') {
+            if let Some(name) = term.take() {
+                args.push((name.trim_matches('`').to_string(), vec![]));
+            }
+            if let Some((_, doc)) = args.last_mut() {
+                doc.push(definition.trim_start());
+                in_definition = true;
+            }
+        } else if line.trim().is_empty() {
+            if in_definition {
+                if let Some((_, doc)) = args.last_mut() {
+                    doc.push("");
+                }
+            }
+        } else if in_definition && line.starts_with([' ', '\t']) {
+            // Continuation of the definition
+            if let Some((_, doc)) = args.last_mut() {
+                doc.push(line);
+            }
+        } else {
+            term = Some(line.trim());
+            in_definition = false;
+        }
+    }
+
+    args.into_iter()
+        .map(|(name, doc)| {
+            Argument::Flat(SingleArg {
+                name,
+                doc: handle_indentation(&doc.join("\n")),
+                default: None,
+                optional: false,
+            })
+        })
+        .collect()
+}
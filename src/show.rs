@@ -0,0 +1,308 @@
+//! This module implements looking up a single entry by its attribute path
+//! and rendering it for the terminal, see `nixdoc show`.
+
+use crate::{
+    commonmark::{Argument, ManualEntry, SingleArg},
+    format::{fenced_div, CodeFences},
+    NixdocError,
+};
+
+/// Minimum similarity of a name to the looked up path for it to be
+/// suggested, see [strsim::jaro_winkler].
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// Maximum number of suggested names.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Find the entry named by an attribute path, which is any of the names
+/// of an entry, e.g. `lib.strings.splitString` or `splitString`.
+///
+/// If no entry is named by the path, the error suggests the names of the
+/// most similar entries.
+pub fn find_entry<'a>(
+    entries: &'a [ManualEntry],
+    path: &str,
+) -> Result<&'a ManualEntry, NixdocError> {
+    let found: Vec<&ManualEntry> = entries
+        .iter()
+        .filter(|entry| entry.names().iter().any(|name| name == path))
+        .collect();
+    match found.as_slice() {
+        [entry] => return Ok(entry),
+        [] => {}
+        found => {
+            return Err(NixdocError::AmbiguousEntry {
+                path: path.to_string(),
+                candidates: found.iter().map(|entry| entry.names()[0].clone()).collect(),
+            })
+        }
+    }
+
+    // Suggest the most similar name of each entry.
+    let mut similar: Vec<(f64, String)> = entries
+        .iter()
+        .filter_map(|entry| {
+            entry
+                .names()
+                .into_iter()
+                .map(|name| (strsim::jaro_winkler(path, &name), name))
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+        })
+        .filter(|(similarity, _)| *similarity >= SUGGESTION_THRESHOLD)
+        .collect();
+    similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Err(NixdocError::UnknownEntry {
+        path: path.to_string(),
+        suggestions: similar
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, name)| name)
+            .collect(),
+    })
+}
+
+/// ANSI styles of the terminal output.
+const BOLD: &str = "1";
+const TYPE: &str = "36";
+const CODE: &str = "33";
+const EXAMPLE: &str = "32";
+
+/// Render an entry for the terminal, wrapped to `width` columns.
+///
+/// The title, type signature, description, arguments, examples and other
+/// sections of doc-comments are rendered in this order. With `color`, they
+/// are styled with ANSI escape sequences, highlighting the type signature,
+/// inline code and examples.
+pub fn render_entry(entry: &ManualEntry, width: usize, color: bool) -> String {
+    let terminal = Terminal { width, color };
+    let mut output = String::new();
+    let (_, title) = entry.get_ident_title();
+    output.push_str(&format!("{}\n", terminal.paint(BOLD, &title)));
+
+    if let Some(fn_type) = &entry.fn_type {
+        output.push('\n');
+        for line in fn_type.trim().lines() {
+            output.push_str(&format!("  {}\n", terminal.paint(TYPE, line)));
+        }
+    }
+
+    let prose = entry.prose();
+    if !prose.trim().is_empty() {
+        output.push('\n');
+        output.push_str(&terminal.markdown(&prose, ""));
+    }
+
+    if entry.lists_args() {
+        output.push_str(&format!("\n{}\n", terminal.paint(BOLD, "Arguments")));
+        for arg in &entry.rendered_args() {
+            match arg {
+                Argument::Flat(arg) => output.push_str(&terminal.argument(arg, "  ")),
+                Argument::Pattern {
+                    entries,
+                    ellipsis,
+                    bind,
+                } => {
+                    output.push_str("  structured function argument");
+                    if let Some(bind) = bind {
+                        output.push_str(&format!(", bound to {}", terminal.paint(CODE, bind)));
+                    }
+                    output.push('\n');
+                    for arg in entries {
+                        output.push_str(&terminal.argument(arg, "    "));
+                    }
+                    if *ellipsis {
+                        output.push_str(&format!(
+                            "    {}\n{}",
+                            terminal.paint(CODE, "..."),
+                            terminal.markdown("Other attributes are accepted too", "        ")
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    let examples = entry.example_blocks();
+    if !examples.is_empty() {
+        let heading = if examples.len() == 1 {
+            "Example"
+        } else {
+            "Examples"
+        };
+        output.push_str(&format!("\n{}\n", terminal.paint(BOLD, heading)));
+        for (index, example) in examples.iter().enumerate() {
+            if index > 0 {
+                output.push('\n');
+            }
+            for line in example.code.trim().lines() {
+                output.push_str(&format!("  {}\n", terminal.paint(EXAMPLE, line)));
+            }
+        }
+    }
+
+    for (heading, section) in &entry.sections {
+        output.push_str(&format!("\n{}\n", terminal.paint(BOLD, heading)));
+        output.push_str(&terminal.markdown(section, "  "));
+    }
+
+    if let Some(location) = &entry.location {
+        output.push('\n');
+        output.push_str(&terminal.markdown(&format!("Located at {}.", location), ""));
+    }
+
+    output
+}
+
+/// Renders text for a terminal of a given width.
+struct Terminal {
+    width: usize,
+    color: bool,
+}
+
+impl Terminal {
+    /// Style text with an ANSI style, e.g. [BOLD].
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Render an argument and its documentation.
+    fn argument(&self, arg: &SingleArg, indent: &str) -> String {
+        let mut output = format!("{}{}\n", indent, self.paint(CODE, &arg.term()));
+        if let Some(doc) = &arg.doc {
+            output.push_str(&self.markdown(doc, &format!("{}    ", indent)));
+        }
+        if let Some(default) = arg.multiline_default() {
+            let default = format!("Default:\n\n```nix\n{}\n```", default);
+            output.push_str(&self.markdown(&default, &format!("{}    ", indent)));
+        }
+        output
+    }
+
+    /// Render CommonMark as wrapped text, indented by `indent`.
+    ///
+    /// Paragraphs and list items are wrapped, code blocks are indented and
+    /// highlighted, and headings are emphasized.
+    fn markdown(&self, markdown: &str, indent: &str) -> String {
+        let mut output = String::new();
+        let mut fences = CodeFences::default();
+        // The list marker (e.g. `- `) and lines of the paragraph or list
+        // item being collected.
+        let mut item: Option<(String, Vec<&str>)> = None;
+
+        for line in markdown.lines() {
+            let trimmed = line.trim();
+            if fences.update(line) {
+                self.flush(&mut item, indent, &mut output);
+                if !trimmed.starts_with("```") && !trimmed.starts_with("~~~") {
+                    output.push_str(&format!("{}  {}\n", indent, self.paint(EXAMPLE, line)));
+                }
+            } else if trimmed.is_empty() {
+                self.flush(&mut item, indent, &mut output);
+                if !output.is_empty() && !output.ends_with("\n\n") {
+                    output.push('\n');
+                }
+            } else if fenced_div(line).is_some() {
+                self.flush(&mut item, indent, &mut output);
+            } else if trimmed.starts_with('#') {
+                self.flush(&mut item, indent, &mut output);
+                let heading = trimmed.trim_start_matches('#').trim();
+                let heading = match heading.rfind('{') {
+                    Some(start) if heading.ends_with('}') => heading[..start].trim_end(),
+                    _ => heading,
+                };
+                output.push_str(&format!("{}{}\n", indent, self.paint(BOLD, heading)));
+            } else if let Some(marker) = list_marker(trimmed) {
+                self.flush(&mut item, indent, &mut output);
+                item = Some((marker.to_string(), vec![&trimmed[marker.len()..]]));
+            } else {
+                match &mut item {
+                    Some((_, lines)) => lines.push(trimmed),
+                    None => item = Some((String::new(), vec![trimmed])),
+                }
+            }
+        }
+        self.flush(&mut item, indent, &mut output);
+        output.truncate(output.trim_end().len());
+        output.push('\n');
+        output
+    }
+
+    /// Write a collected paragraph or list item, wrapped to the width.
+    fn flush(&self, item: &mut Option<(String, Vec<&str>)>, indent: &str, output: &mut String) {
+        let Some((marker, lines)) = item.take() else {
+            return;
+        };
+        let text = self.inline(&lines.join(" "));
+        let initial_indent = format!("{}{}", indent, marker);
+        let subsequent_indent = format!("{}{}", indent, " ".repeat(marker.chars().count()));
+        let options = textwrap::Options::new(self.width)
+            .initial_indent(&initial_indent)
+            .subsequent_indent(&subsequent_indent);
+        output.push_str(&textwrap::fill(&text, options));
+        output.push('\n');
+    }
+
+    /// Render inline CommonMark: code spans are highlighted without their
+    /// backticks, and links are written as their text followed by their
+    /// target, e.g. `text <https://example.com>`.
+    fn inline(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+        loop {
+            let code = rest.find('`');
+            let link = rest.find('[');
+            match (code, link) {
+                (Some(start), _) if link.is_none_or(|link| start < link) => {
+                    let Some(length) = rest[start + 1..].find('`') else {
+                        break;
+                    };
+                    result.push_str(&rest[..start]);
+                    result.push_str(&self.paint(CODE, &rest[start + 1..start + 1 + length]));
+                    rest = &rest[start + length + 2..];
+                }
+                (_, Some(start)) => {
+                    let link = rest[start..].find("](").and_then(|middle| {
+                        let end = rest[start + middle..].find(')')?;
+                        Some((start + middle, start + middle + end))
+                    });
+                    let Some((middle, end)) = link else {
+                        // A bracket which does not start a link, e.g. of a list.
+                        result.push_str(&rest[..=start]);
+                        rest = &rest[start + 1..];
+                        continue;
+                    };
+                    let target = &rest[middle + 2..end];
+                    result.push_str(&rest[..start]);
+                    result.push_str(&self.inline(&rest[start + 1..middle]));
+                    // Anchors of the documentation have no target here.
+                    if !target.starts_with('#') {
+                        result.push_str(&format!(" <{}>", target));
+                    }
+                    rest = &rest[end + 1..];
+                }
+                _ => break,
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Returns the marker of a list item or definition, e.g. `- ` or `1. `.
+fn list_marker(line: &str) -> Option<&str> {
+    if ["- ", "* ", "+ ", ": "]
+        .iter()
+        .any(|marker| line.starts_with(marker))
+    {
+        return Some(&line[..2]);
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return Some(&line[..digits + 2]);
+    }
+    None
+}
@@ -0,0 +1,393 @@
+//! This module implements parsing of the type signatures in doc comments,
+//! e.g. `concatMapStrings :: (a -> string) -> [a] -> string`.
+//!
+//! There is no formal definition of these signatures. The syntax follows
+//! the Haskell-like conventions of nixpkgs:
+//!
+//! - type variables are single lower-case letters, e.g. `a` or `b'`;
+//!   other names such as `string` or `AttrSet` are named types, which may
+//!   be applied to arguments, e.g. `attrsOf string`;
+//! - `[a]` is a list of `a`;
+//! - `{ name :: string; value :: a; }` is an attribute set, which may end
+//!   in `...` if it can contain other attributes; `=` is accepted in
+//!   place of `::`, and `${name}` as an attribute name;
+//! - `a | b` is a union of types;
+//! - `a -> b` is a function, associating to the right.
+
+use serde::Serialize;
+use std::fmt;
+
+/// A parsed type signature.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Signature {
+    /// The name the signature is given for, i.e. the part before `::`.
+    pub name: Option<String>,
+
+    #[serde(rename = "type")]
+    pub ty: Type,
+}
+
+/// A type in a type signature.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Type {
+    /// A type variable, e.g. `a`.
+    Var { name: String },
+
+    /// A named type and its arguments, e.g. `string` or `attrsOf a`.
+    Named { name: String, args: Vec<Type> },
+
+    /// A list, e.g. `[a]`.
+    List { element: Box<Type> },
+
+    /// An attribute set, e.g. `{ name :: string; ... }`.
+    Record { fields: Vec<Field>, open: bool },
+
+    /// A function, e.g. `a -> b`.
+    Function {
+        argument: Box<Type>,
+        result: Box<Type>,
+    },
+
+    /// A union of types, e.g. `string | path`.
+    Union { alternatives: Vec<Type> },
+}
+
+/// An attribute of an attribute set type.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub ty: Type,
+
+    /// Whether the attribute may be missing, written `name ? :: type`.
+    pub optional: bool,
+}
+
+impl Type {
+    /// The number of arguments a value of this type can be applied to,
+    /// i.e. the number of arrows on the right-hand spine of the type.
+    pub fn arity(&self) -> usize {
+        match self {
+            Type::Function { result, .. } => 1 + result.arity(),
+            _ => 0,
+        }
+    }
+}
+
+/// A syntax error in a type signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureError {
+    pub message: String,
+
+    /// Byte offset of the error in the signature.
+    pub offset: usize,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    /// An interpolated attribute name, e.g. `${name}`.
+    Interpolation(String),
+    DoubleColon,
+    Arrow,
+    Pipe,
+    Ellipsis,
+    Punct(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) | Token::Interpolation(name) => write!(f, "`{}`", name),
+            Token::DoubleColon => write!(f, "`::`"),
+            Token::Arrow => write!(f, "`->`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::Ellipsis => write!(f, "`...`"),
+            Token::Punct(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '\'' | '.')
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, SignatureError> {
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        let rest = &src[offset..];
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            c if is_ident_start(c) => {
+                let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+                Token::Ident(rest[..len].to_string())
+            }
+            '$' if rest.starts_with("${") => {
+                let Some(len) = rest.find('}') else {
+                    return Err(SignatureError {
+                        message: "unterminated `${`".to_string(),
+                        offset,
+                    });
+                };
+                Token::Interpolation(rest[..=len].to_string())
+            }
+            ':' if rest.starts_with("::") => Token::DoubleColon,
+            '-' if rest.starts_with("->") => Token::Arrow,
+            '.' if rest.starts_with("...") => Token::Ellipsis,
+            '|' => Token::Pipe,
+            '[' | ']' | '(' | ')' | '{' | '}' | ';' | ',' | '=' | '?' => Token::Punct(c),
+            c => {
+                return Err(SignatureError {
+                    message: format!("unexpected character `{}`", c),
+                    offset,
+                })
+            }
+        };
+
+        // Skip the remaining characters of multi-character tokens.
+        let len = match &token {
+            Token::Ident(s) | Token::Interpolation(s) => s.len(),
+            Token::DoubleColon | Token::Arrow => 2,
+            Token::Ellipsis => 3,
+            Token::Pipe | Token::Punct(_) => 1,
+        };
+        while chars.peek().is_some_and(|(next, _)| *next < offset + len) {
+            chars.next();
+        }
+        tokens.push((offset, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(offset, _)| *offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, SignatureError> {
+        let found = match self.peek() {
+            Some(token) => token.to_string(),
+            None => "end of signature".to_string(),
+        };
+        Err(SignatureError {
+            message: format!("expected {}, found {}", expected, found),
+            offset: self.offset(),
+        })
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), SignatureError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            self.error(&token.to_string())
+        }
+    }
+
+    /// function := union ('->' function)?
+    fn function(&mut self) -> Result<Type, SignatureError> {
+        let argument = self.union()?;
+        if self.eat(&Token::Arrow) {
+            Ok(Type::Function {
+                argument: Box::new(argument),
+                result: Box::new(self.function()?),
+            })
+        } else {
+            Ok(argument)
+        }
+    }
+
+    /// union := application ('|' application)*
+    fn union(&mut self) -> Result<Type, SignatureError> {
+        let mut alternatives = vec![self.application()?];
+        while self.eat(&Token::Pipe) {
+            alternatives.push(self.application()?);
+        }
+        if alternatives.len() == 1 {
+            Ok(alternatives.remove(0))
+        } else {
+            Ok(Type::Union { alternatives })
+        }
+    }
+
+    /// application := name atom* | atom
+    fn application(&mut self) -> Result<Type, SignatureError> {
+        let Some(Token::Ident(name)) = self.peek().cloned() else {
+            return self.atom();
+        };
+        self.position += 1;
+
+        let mut args = vec![];
+        while matches!(
+            self.peek(),
+            Some(Token::Ident(_) | Token::Punct('[' | '(' | '{'))
+        ) {
+            args.push(self.atom()?);
+        }
+        if args.is_empty() && is_type_variable(&name) {
+            Ok(Type::Var { name })
+        } else {
+            Ok(Type::Named { name, args })
+        }
+    }
+
+    /// atom := name | '[' function ']' | '(' function ')' | record
+    fn atom(&mut self) -> Result<Type, SignatureError> {
+        match self.peek() {
+            Some(Token::Ident(_)) => {
+                let Some(Token::Ident(name)) = self.next() else {
+                    unreachable!()
+                };
+                if is_type_variable(&name) {
+                    Ok(Type::Var { name })
+                } else {
+                    Ok(Type::Named { name, args: vec![] })
+                }
+            }
+            Some(Token::Punct('[')) => {
+                self.position += 1;
+                let element = self.function()?;
+                self.expect(Token::Punct(']'))?;
+                Ok(Type::List {
+                    element: Box::new(element),
+                })
+            }
+            Some(Token::Punct('(')) => {
+                self.position += 1;
+                let ty = self.function()?;
+                self.expect(Token::Punct(')'))?;
+                Ok(ty)
+            }
+            Some(Token::Punct('{')) => {
+                self.position += 1;
+                self.record()
+            }
+            _ => self.error("a type"),
+        }
+    }
+
+    /// record := (field (';' | ','))* (field | '...')? '}'
+    fn record(&mut self) -> Result<Type, SignatureError> {
+        let mut fields = vec![];
+        let mut open = false;
+
+        loop {
+            match self.next() {
+                Some(Token::Punct('}')) => break,
+                Some(Token::Ellipsis) => {
+                    open = true;
+                    self.eat(&Token::Punct(';'));
+                    self.eat(&Token::Punct(','));
+                    self.expect(Token::Punct('}'))?;
+                    break;
+                }
+                Some(Token::Ident(name) | Token::Interpolation(name)) => {
+                    let optional = self.eat(&Token::Punct('?'));
+                    if !self.eat(&Token::DoubleColon) && !self.eat(&Token::Punct('=')) {
+                        return self.error("`::`");
+                    }
+                    fields.push(Field {
+                        name,
+                        ty: self.function()?,
+                        optional,
+                    });
+                    if !self.eat(&Token::Punct(';')) && !self.eat(&Token::Punct(',')) {
+                        self.expect(Token::Punct('}'))?;
+                        break;
+                    }
+                }
+                _ => {
+                    self.position -= 1;
+                    return self.error("an attribute name or `}`");
+                }
+            }
+        }
+
+        Ok(Type::Record { fields, open })
+    }
+}
+
+/// Type variables are single lower-case letters, optionally followed by
+/// digits or primes, e.g. `a`, `b1` or `a'`.
+fn is_type_variable(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_digit() || c == '\'')
+}
+
+/// Parse a type signature, optionally preceded by the name it is given
+/// for, e.g. `map :: (a -> b) -> [a] -> [b]` or `string -> bool`.
+///
+/// The signature ends at the first empty line. Legacy comments do not
+/// delimit their `Type:` otherwise, so it may be followed by prose.
+pub fn parse_signature(src: &str) -> Result<Signature, SignatureError> {
+    let mut end = 0;
+    for line in src.split_inclusive('\n') {
+        if line.trim().is_empty() && !src[..end].trim().is_empty() {
+            break;
+        }
+        end += line.len();
+    }
+    let src = &src[..end];
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        position: 0,
+        end: src.len(),
+    };
+
+    let name = match parser.tokens.as_slice() {
+        [(_, Token::Ident(name)), (_, Token::DoubleColon), ..] => {
+            parser.position = 2;
+            Some(name.clone())
+        }
+        _ => None,
+    };
+    let ty = parser.function()?;
+    if parser.peek().is_some() {
+        return parser.error("end of signature");
+    }
+
+    Ok(Signature { name, ty })
+}
@@ -1,73 +1,43 @@
-use rnix;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
-    collect_entries, format::shift_headings, main_with_options, retrieve_description, ManualEntry,
-    Options,
+    anchors::{validate_anchors, Document},
+    cache::Cache,
+    collect_entries,
+    config::{Config, Overrides, Target},
+    coverage::collect_coverage,
+    examples::{check_examples, extract_examples, render_tests},
+    format::{shift_headings, single_line_expression},
+    html::{render_site, SiteCategory},
+    links::{link_references, LinkIndex},
+    lint::lint,
+    location::{line_column, LineIndex},
+    man::{render_man, render_man_pages},
+    migrate::migrate,
+    parse, render_commonmark, render_json, render_search_index, retrieve_description,
+    show::{find_entry, render_entry},
+    signature::parse_signature,
+    CollectOptions, Format, JsonVersion, ManualEntry, NixdocError,
 };
 
-#[test]
-fn test_main() {
-    let options = Options {
-        prefix: String::from("lib"),
-        anchor_prefix: String::from("function-library-"),
-        json_output: false,
-        category: String::from("strings"),
-        description: String::from("string manipulation functions"),
-        file: PathBuf::from("test/strings.nix"),
-        locs: Some(PathBuf::from("test/strings.json")),
-    };
-
-    let output = main_with_options(options);
-
-    insta::assert_snapshot!(output);
-}
-
-#[test]
-fn test_main_minimal() {
-    let options = Options {
-        prefix: String::from(""),
-        anchor_prefix: String::from(""),
-        json_output: false,
-        category: String::from(""),
-        description: String::from(""),
-        file: PathBuf::from("test/strings.nix"),
-        locs: Some(PathBuf::from("test/strings.json")),
-    };
-
-    let output = main_with_options(options);
-
-    insta::assert_snapshot!(output);
-}
-
-#[test]
-fn test_json_output() {
-    let options = Options {
-        prefix: String::from("lib"),
-        anchor_prefix: String::from("function-library-"),
-        json_output: true,
-        category: String::from("strings"),
-        description: String::from("string manipulation functions"),
-        file: PathBuf::from("test/strings.nix"),
-        locs: Some(PathBuf::from("test/strings.json")),
-    };
-
-    let output = main_with_options(options);
-
-    insta::assert_snapshot!(output);
-}
-
 #[test]
 fn test_description_of_lib_debug() {
     let src = fs::read_to_string("test/lib-debug.nix").unwrap();
     let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
     let prefix = "lib";
     let category = "debug";
-    let desc = retrieve_description(&nix, &"Debug", category);
-    let mut output = String::from(desc) + "\n";
-
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    let desc = retrieve_description(&nix, "Debug", category);
+    let mut output = desc + "\n";
+
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
@@ -82,13 +52,50 @@ fn test_arg_formatting() {
     let prefix = "lib";
     let category = "options";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
     insta::assert_snapshot!(output);
 }
 
+#[test]
+fn test_arg_defaults() {
+    let mut output = String::from("");
+    let src = fs::read_to_string("test/arg-defaults.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: "lib".to_string(),
+            category: "options".to_string(),
+            ..Default::default()
+        },
+    ) {
+        entry.write_section("function-library-", &mut output);
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_single_line_expression() {
+    assert_eq!(
+        single_line_expression("{\n  a = \"x  y\";\n  b = [ 1\n    2 ];\n}").as_deref(),
+        Some("{ a = \"x  y\"; b = [ 1 2 ]; }")
+    );
+    assert_eq!(single_line_expression("''\n  a\n''"), None);
+    assert_eq!(single_line_expression("1 # one\n"), None);
+}
+
 #[test]
 fn test_inherited_exports() {
     let mut output = String::from("");
@@ -97,7 +104,14 @@ fn test_inherited_exports() {
     let prefix = "lib";
     let category = "let";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
@@ -112,7 +126,14 @@ fn test_line_comments() {
     let prefix = "lib";
     let category = "let";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
@@ -127,7 +148,14 @@ fn test_multi_line() {
     let prefix = "lib";
     let category = "let";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
@@ -142,7 +170,14 @@ fn test_doc_comment() {
     let prefix = "lib";
     let category = "debug";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
@@ -173,10 +208,17 @@ fn test_doc_comment_section_description() {
     let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
     let prefix = "lib";
     let category = "debug";
-    let desc = retrieve_description(&nix, &"Debug", category);
-    let mut output = String::from(desc) + "\n";
-
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    let desc = retrieve_description(&nix, "Debug", category);
+    let mut output = desc + "\n";
+
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
@@ -189,27 +231,96 @@ fn test_doc_comment_no_duplicate_arguments() {
     let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
     let prefix = "lib";
     let category = "debug";
-    let desc = retrieve_description(&nix, &"Debug", category);
-    let mut output = String::from(desc) + "\n";
-
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    let desc = retrieve_description(&nix, "Debug", category);
+    let mut output = desc + "\n";
+
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
         entry.write_section("function-library-", &mut output);
     }
 
     insta::assert_snapshot!(output);
 }
 
+#[test]
+fn test_doc_comment_lambda_args() {
+    let src = fs::read_to_string("test/doc-comment-lambda-args.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "things".to_string(),
+            ..Default::default()
+        },
+    );
+
+    insta::assert_snapshot!(render_commonmark("", entries, "function-library-"));
+}
+
+#[test]
+fn test_pattern_defaults() {
+    let src = fs::read_to_string("test/pattern-defaults.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "packages".to_string(),
+            ..Default::default()
+        },
+    );
+
+    let markdown = render_commonmark("", entries.clone(), "function-library-");
+    let json = render_json(entries, JsonVersion::V2).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    insta::assert_snapshot!(format!(
+        "{}\n{}",
+        markdown,
+        serde_json::to_string_pretty(&json["entries"]).unwrap()
+    ));
+}
+
+#[test]
+fn test_json_default_version() {
+    let src = fs::read_to_string("test/strings.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "strings".to_string(),
+            ..Default::default()
+        },
+    );
+
+    // Version 2 is opt-in, plain `--json-output` keeps emitting version 1.
+    let json: serde_json::Value =
+        serde_json::from_str(&render_json(entries, JsonVersion::default()).unwrap()).unwrap();
+    assert_eq!(json["version"], 1);
+}
+
 #[test]
 fn test_empty_prefix() {
     let test_entry = ManualEntry {
         args: vec![],
+        arguments_section: false,
         category: "test".to_string(),
         location: None,
         description: vec![],
         example: None,
+        examples: vec![],
+        sections: Default::default(),
+        format: Default::default(),
         fn_type: None,
-        name: "mapSimple'".to_string(),
+        attr_path: vec!["mapSimple'".to_string()],
+        depth: 0,
         prefix: "".to_string(),
+        inherited_from: None,
+        span: None,
     };
 
     let (ident, title) = test_entry.get_ident_title();
@@ -226,9 +337,605 @@ fn test_patterns() {
     let prefix = "lib";
     let category = "debug";
 
-    for entry in collect_entries(nix, prefix, category, &Default::default()) {
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    ) {
+        entry.write_section("function-library-", &mut output);
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_nested_attrsets() {
+    let mut output = String::from("");
+    let src = fs::read_to_string("test/nested-attrsets.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let prefix = "lib";
+    let category = "nested";
+
+    for entry in collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            max_depth: 3,
+            ..Default::default()
+        },
+    ) {
+        entry.write_section("function-library-", &mut output);
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_inherit_from() {
+    let mut output = String::from("");
+    let file = PathBuf::from("test/inherit-from.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let prefix = "lib";
+    let category = "inherit";
+
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            file: Some(file),
+            ..Default::default()
+        },
+    );
+    let sources: Vec<_> = entries
+        .iter()
+        .map(|entry| entry.inherited_from.as_deref())
+        .collect();
+    assert_eq!(
+        sources,
+        vec![
+            Some("helpers"),
+            Some("helpers.nested"),
+            Some("{ /** Documented in a literal attribute set. */ literal = a: a; }"),
+            Some("helpers"),
+            Some("helpers"),
+            Some("strings"),
+            Some("import ./inherit-from/strings.nix { inherit lib; }"),
+        ]
+    );
+
+    for entry in entries {
         entry.write_section("function-library-", &mut output);
     }
 
     insta::assert_snapshot!(output);
 }
+
+#[test]
+fn test_line_column() {
+    let src = "{\n  föö = 1;\n}\n";
+
+    assert_eq!(line_column(src, 0), (1, 1));
+    assert_eq!(line_column(src, 4), (2, 3));
+    // columns count characters, not bytes
+    assert_eq!(line_column(src, 9), (2, 6));
+    assert_eq!(line_column(src, 100), (4, 1));
+
+    let lines = LineIndex::new(src);
+    for offset in 0..=src.len() + 1 {
+        assert_eq!(lines.line_column(offset), line_column(src, offset));
+    }
+}
+
+#[test]
+fn test_parse_error() {
+    let error = parse("{\n  foo = ;\n}\n").unwrap_err();
+
+    let NixdocError::Parse { ref location, .. } = error else {
+        panic!("expected a parse error, got {error:?}");
+    };
+    assert_eq!((location.line, location.column), (2, 9));
+    assert_eq!(location.range, 10..11);
+    assert_eq!(error.exit_code(), 65);
+    assert!(error
+        .to_string()
+        .ends_with(" --> 2:9\n  |\n2 |   foo = ;\n  |         ^"));
+}
+
+#[test]
+fn test_doc_comment_sections() {
+    let src = fs::read_to_string("test/doc-comment-sections.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let prefix = "lib";
+    let category = "strings";
+
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: prefix.to_string(),
+            category: category.to_string(),
+            ..Default::default()
+        },
+    );
+
+    insta::assert_snapshot!(serde_json::to_string_pretty(&entries).unwrap());
+}
+
+#[test]
+fn test_coverage() {
+    let file = PathBuf::from("test/coverage.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+
+    let coverage = collect_coverage(
+        &nix,
+        &CollectOptions {
+            category: "coverage".to_string(),
+            file: Some(file),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(coverage.documented(), 4);
+    assert_eq!(coverage.bindings.len(), 7);
+    insta::assert_snapshot!(coverage.render_text());
+}
+
+#[test]
+fn test_lint_arguments() {
+    let file = PathBuf::from("test/lint.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+
+    let diagnostics: Vec<String> = lint(&nix, Some(&file), 1, &LineIndex::new(&src))
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+
+    insta::assert_snapshot!(diagnostics.join("\n"));
+}
+
+#[test]
+fn test_parse_signature() {
+    let signatures = [
+        "concatMapStrings :: (a -> string) -> [a] -> string",
+        "attrsOf (string | listOf string | attrsOf string) -> string",
+        "{ name :: String; value ? :: a; ... } -> b'",
+        "runTests :: { tests = [ String ]; ${testName} :: { expr :: a; expected :: a; }; } -> [ { name :: String; } ]",
+    ];
+
+    let output: Vec<String> = signatures
+        .iter()
+        .map(|src| {
+            let signature = parse_signature(src).unwrap();
+            format!("{}\n{}", src, serde_json::to_string(&signature).unwrap())
+        })
+        .collect();
+
+    insta::assert_snapshot!(output.join("\n\n"));
+}
+
+#[test]
+fn test_signature_arity_and_errors() {
+    let arities = [("a -> b -> c", 2), ("(a -> b) -> c", 1), ("[a -> b]", 0)];
+    for (src, arity) in arities {
+        assert_eq!(parse_signature(src).unwrap().ty.arity(), arity, "{}", src);
+    }
+
+    let error = parse_signature("escapeC = [string] -> string").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected end of signature, found `=` at offset 8"
+    );
+
+    let error = parse_signature("{ name :: string").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected `}`, found end of signature at offset 16"
+    );
+
+    // Prose following the signature of a legacy comment is ignored.
+    let signature = parse_signature("mesonOption :: string -> string\n\n@param feature").unwrap();
+    assert_eq!(signature.name.as_deref(), Some("mesonOption"));
+}
+
+#[test]
+fn test_cross_links() {
+    let src = fs::read_to_string("test/cross-links.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let anchor_prefix = "function-library-";
+
+    let mut entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "strings".to_string(),
+            ..Default::default()
+        },
+    );
+    let mut index = LinkIndex::new(&entries, anchor_prefix);
+    index.extend_external(
+        [(
+            "lib.attrsets.mapAttrs".to_string(),
+            "https://example.com/attrsets#mapAttrs".to_string(),
+        )]
+        .into(),
+    );
+    link_references(&mut entries, &index, anchor_prefix);
+
+    insta::assert_snapshot!(render_commonmark("", entries, anchor_prefix));
+}
+
+#[test]
+fn test_render_site() {
+    let anchor_prefix = "function-library-";
+    let mut categories: Vec<SiteCategory> = [
+        ("test/cross-links.nix", "strings", "String functions"),
+        ("test/site-lists.nix", "lists", "List functions"),
+    ]
+    .into_iter()
+    .map(|(file, category, title)| {
+        let src = fs::read_to_string(file).unwrap();
+        let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+        SiteCategory {
+            name: category.to_string(),
+            title: title.to_string(),
+            description: retrieve_description(&nix, title, category),
+            entries: collect_entries(
+                nix,
+                &CollectOptions {
+                    category: category.to_string(),
+                    ..Default::default()
+                },
+            ),
+        }
+    })
+    .collect();
+    let index = LinkIndex::new(
+        categories.iter().flat_map(|category| &category.entries),
+        anchor_prefix,
+    );
+    for category in &mut categories {
+        link_references(&mut category.entries, &index, anchor_prefix);
+    }
+
+    let output: String = render_site("lib", &categories, anchor_prefix)
+        .into_iter()
+        .filter(|(path, _)| path != "style.css")
+        .map(|(path, contents)| format!("==> {} <==\n{}\n", path, contents))
+        .collect();
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_render_man() {
+    let src = fs::read_to_string("test/doc-comment-sections.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let description = retrieve_description(&nix, "String functions", "strings");
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "strings".to_string(),
+            ..Default::default()
+        },
+    );
+    let mut output = render_man("lib.strings", &description, entries);
+
+    let src = fs::read_to_string("test/pattern-defaults.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "packages".to_string(),
+            ..Default::default()
+        },
+    );
+    for (path, contents) in render_man_pages(&entries) {
+        output.push_str(&format!("==> {} <==\n{}", path, contents));
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_show() {
+    let mut entries = vec![];
+    for (file, category) in [
+        ("test/strings.nix", "strings"),
+        ("test/doc-comment-sections.nix", "text"),
+        ("test/pattern-defaults.nix", "packages"),
+    ] {
+        let src = fs::read_to_string(file).unwrap();
+        let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+        entries.extend(collect_entries(
+            nix,
+            &CollectOptions {
+                category: category.to_string(),
+                ..Default::default()
+            },
+        ));
+    }
+
+    let mut output = String::new();
+    for path in [
+        "lib.strings.concatMapStrings",
+        "text.concatStringsSep",
+        "mkPackage",
+    ] {
+        let entry = find_entry(&entries, path).unwrap();
+        output.push_str(&format!("$ {}\n{}\n", path, render_entry(entry, 60, false)));
+    }
+    for path in ["strings.concatMapString", "concatStringsSep", "lib.nothing"] {
+        let error = find_entry(&entries, path).unwrap_err();
+        output.push_str(&format!("$ {}\n{}\n\n", path, error));
+    }
+
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_validate_anchors() {
+    let file = PathBuf::from("test/anchors.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let category = "anchors";
+
+    let description = retrieve_description(&nix, "Anchors", category);
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: "lib".to_string(),
+            category: category.to_string(),
+            file: Some(file.clone()),
+            ..Default::default()
+        },
+    );
+    let documents = [Document {
+        file: Some(&file),
+        description: &description,
+        entries: &entries,
+    }];
+
+    let output = validate_anchors(&documents, "function-library-", None)
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_extract_examples() {
+    let file = PathBuf::from("test/examples.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: "lib".to_string(),
+            category: "strings".to_string(),
+            file: Some(file),
+            ..Default::default()
+        },
+    );
+    let examples = extract_examples(&src, Some(Path::new("test/examples.nix")), &entries);
+
+    let mut output = render_tests(&examples.examples);
+    for diagnostic in examples.diagnostics {
+        output.push_str(&format!("\n{}", diagnostic));
+    }
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_check_examples() {
+    let file = PathBuf::from("test/example-syntax.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            prefix: "lib".to_string(),
+            category: "trivial".to_string(),
+            file: Some(file),
+            ..Default::default()
+        },
+    );
+
+    let output = check_examples(&src, Some(Path::new("test/example-syntax.nix")), &entries)
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    insta::assert_snapshot!(output);
+}
+
+#[test]
+fn test_inherited_examples() {
+    let file = PathBuf::from("test/inherit-examples.nix");
+    let src = fs::read_to_string(&file).unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let entries = collect_entries(
+        nix,
+        &CollectOptions {
+            category: "greetings".to_string(),
+            file: Some(file.clone()),
+            ..Default::default()
+        },
+    );
+
+    // The examples of inherited entries are located in the file they are
+    // inherited from, although the same examples occur in this file.
+    let imported = Some(PathBuf::from("test/inherit-examples/greetings.nix"));
+    let locations: Vec<_> = extract_examples(&src, Some(&file), &entries)
+        .examples
+        .into_iter()
+        .map(|example| (example.location.path, example.location.line))
+        .collect();
+    assert_eq!(
+        locations,
+        vec![(Some(file.clone()), 8), (imported.clone(), 10)]
+    );
+
+    let locations: Vec<_> = check_examples(&src, Some(&file), &entries)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.location.path, diagnostic.location.line))
+        .collect();
+    assert_eq!(locations, vec![(Some(file), 13), (imported, 22)]);
+}
+
+#[test]
+fn test_migrate() {
+    let src = fs::read_to_string("test/migrate.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+
+    let migrated = migrate(&nix, 1);
+    let nix = rnix::Root::parse(&migrated)
+        .ok()
+        .expect("failed to parse migrated input");
+    assert_eq!(migrate(&nix, 1), migrated);
+
+    insta::assert_snapshot!(migrated);
+}
+
+#[test]
+fn test_migrate_function_body() {
+    let src = fs::read_to_string("test/migrate.nix").unwrap();
+    let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+    let migrated = migrate(&nix, 1);
+
+    // The body of `mkConfig` follows its migrated doc comment unchanged.
+    let body = &src[src.find("mkConfig = name:").unwrap()..];
+    assert!(migrated.contains("/**\n    Build a configuration.\n  */\n  mkConfig"));
+    assert!(migrated.ends_with(body));
+}
+
+#[test]
+fn test_search_index() {
+    let mut entries = vec![];
+    for (file, category) in [
+        ("test/doc-comment-sections.nix", "strings"),
+        ("test/pattern-defaults.nix", "packages"),
+    ] {
+        let src = fs::read_to_string(file).unwrap();
+        let nix = rnix::Root::parse(&src).ok().expect("failed to parse input");
+        entries.extend(collect_entries(
+            nix,
+            &CollectOptions {
+                category: category.to_string(),
+                ..Default::default()
+            },
+        ));
+    }
+
+    let index = render_search_index(&entries, "function-library-", false).unwrap();
+    let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+    assert!(index.get("index").is_none());
+
+    let index = render_search_index(&entries, "function-library-", true).unwrap();
+    let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+    insta::assert_snapshot!(serde_json::to_string_pretty(&index).unwrap());
+}
+
+#[test]
+fn test_config_targets() {
+    let config: Config = r#"
+        prefix = "utils"
+        format = "html"
+        source-url-template = "https://example.com/{path}#L{line}"
+
+        [[file]]
+        path = "strings.nix"
+        category = "strings"
+        output = "docs/strings.html"
+
+        [[file]]
+        path = "lists.nix"
+        description = "List functions"
+        prefix = "lists"
+        anchor-prefix = ""
+        format = "man"
+        max-depth = 2
+        locs = "locs.json"
+    "#
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        config.targets(&Overrides::default()),
+        vec![
+            Target {
+                path: PathBuf::from("strings.nix"),
+                category: "strings".to_string(),
+                description: String::new(),
+                prefix: "utils".to_string(),
+                anchor_prefix: "function-library-".to_string(),
+                format: Format::Html,
+                max_depth: 1,
+                locs: None,
+                source_url_template: Some("https://example.com/{path}#L{line}".to_string()),
+                output: Some(PathBuf::from("docs/strings.html")),
+            },
+            Target {
+                path: PathBuf::from("lists.nix"),
+                category: String::new(),
+                description: "List functions".to_string(),
+                prefix: "lists".to_string(),
+                anchor_prefix: String::new(),
+                format: Format::Man,
+                max_depth: 2,
+                locs: Some(PathBuf::from("locs.json")),
+                source_url_template: Some("https://example.com/{path}#L{line}".to_string()),
+                output: None,
+            },
+        ]
+    );
+
+    // Command line flags take precedence over the configuration.
+    let overrides = Overrides {
+        prefix: Some("lib".to_string()),
+        anchor_prefix: None,
+        format: Some(Format::CommonMark),
+    };
+    let targets = config.targets(&overrides);
+    assert!(targets
+        .iter()
+        .all(|target| target.prefix == "lib" && target.format == Format::CommonMark));
+    assert_eq!(targets[1].anchor_prefix, "");
+
+    let error = "[[file]]\npath = \"a.nix\"\nformat = \"pdf\"\n"
+        .parse::<Config>()
+        .unwrap_err();
+    assert!(error.to_string().contains("unsupported format 'pdf'"));
+    let error = "[[file]]\npath = \"a.nix\"\ncatgory = \"a\"\n"
+        .parse::<Config>()
+        .unwrap_err();
+    assert!(error.to_string().contains("unknown field `catgory`"));
+}
+
+#[test]
+fn test_cache() {
+    assert_eq!(Cache::key(&[b"ab", b"c"]), Cache::key(&[b"ab", b"c"]));
+    assert_ne!(Cache::key(&[b"ab", b"c"]), Cache::key(&[b"a", b"bc"]));
+
+    let dir = std::env::temp_dir().join(format!("nixdoc-test-cache-{}", std::process::id()));
+    let key = Cache::key(&[b"{ }"]);
+    let cache = Cache::open(&dir, true).unwrap();
+    assert_eq!(cache.get(&key), None);
+    cache.put(&key, "documentation").unwrap();
+    assert_eq!(cache.get(&key).as_deref(), Some("documentation"));
+    assert_eq!(
+        Cache::open(&dir, false).unwrap().get(&key).as_deref(),
+        Some("documentation")
+    );
+
+    // Cleaning the cache discards all entries.
+    assert_eq!(Cache::open(&dir, true).unwrap().get(&key), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,40 @@
+/**
+  Functions with colliding anchors.
+
+  See [`foo`](#function-library-lib.anchors.foo) and the
+  [missing function](#function-library-lib.anchors.missing).
+*/
+{
+  /**
+    The function `foo`, see [its prime](#function-library-lib.anchors.foo-prime).
+
+    # Examples {#ex-foo}
+
+    ```nix
+    # [not a link](#in-a-code-block)
+    foo 1
+    ```
+  */
+  foo = x: x;
+
+  /**
+    Collides with `foo-prime`.
+
+    Anchors such as `{#ex-foo}` and links such as ``[foo](#not-a-link)``
+    in code spans are not checked, neither are those of code blocks:
+
+    ```markdown
+    # Examples {#ex-foo}
+    ```
+  */
+  foo' = x: x;
+
+  /**
+    Collides with `foo'`.
+
+    # Examples {#ex-foo}
+
+    See the [example of foo](#ex-foo) and a [typo](#ex-fo "title").
+  */
+  foo-prime = x: x;
+}
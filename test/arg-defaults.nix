@@ -0,0 +1,19 @@
+{
+  /* Defaults are rendered on a single line, strings are kept verbatim */
+  fn =
+    {
+      # spaces within a string
+      separator ? "a  b",
+      # an attribute set over several lines
+      settings ? {
+        enable = true;
+        port = 80;
+      },
+      # an indented string over several lines
+      script ? ''
+        echo  hello
+        echo  world
+      '',
+    }:
+    0;
+}
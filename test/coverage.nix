@@ -0,0 +1,40 @@
+{ lib }:
+
+let
+  /** Documented let binding. */
+  letBound = x: x;
+
+  undocumentedLetBound = x: x;
+in {
+  inherit letBound undocumentedLetBound;
+
+  /**
+    Documents its arguments in a definition list.
+
+    # Arguments
+
+    `a`
+    : The first argument
+
+    `b`
+    : The second argument
+  */
+  rfcArguments = a: b: c: a;
+
+  /** Documents its pattern arguments with doc comments. */
+  rfcPattern = {
+    /** The documented argument */
+    documented,
+    undocumented,
+  }: documented;
+
+  /* Documents its arguments with legacy comments. */
+  legacyArguments =
+    # The documented argument
+    documented:
+    undocumented: documented;
+
+  undocumented = x: x;
+
+  value = 42;
+}
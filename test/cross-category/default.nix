@@ -0,0 +1,5 @@
+{ lib }:
+{
+  strings = import ./strings.nix { inherit lib; };
+  lists = import ./lists.nix { inherit lib; };
+}
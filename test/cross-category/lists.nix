@@ -0,0 +1,5 @@
+{ lib }:
+{
+  /** Return the first element of a list. */
+  head = list: builtins.elemAt list 0;
+}
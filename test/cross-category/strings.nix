@@ -0,0 +1,8 @@
+{ lib }:
+{
+  /**
+    Concatenate a list of strings, see [`lib.lists.head`](#function-library-lib.lists.head)
+    and [`lib.lists.tail`](#function-library-lib.lists.tail).
+  */
+  concatStrings = builtins.concatStringsSep "";
+}
@@ -0,0 +1,30 @@
+{
+  /**
+    Concatenate a list of strings.
+
+    See also `concatMapStrings`, `strings.concatMapStrings` and
+    `lib.strings.concatMapStrings`, as well as `lib.concatMapStrings`.
+    Links to itself, like `concatStrings`, and unknown names, like `foo`,
+    are left alone. So are code spans with multiple backticks: ``concatMapStrings``.
+
+    External names are linked too: `lib.attrsets.mapAttrs`.
+
+    # Examples
+
+    ```nix
+    # `concatMapStrings` in a code block
+    concatStrings [ "a" "b" ]
+
+    => "ab"
+    ```
+  */
+  concatStrings = builtins.concatStringsSep "";
+
+  /**
+    Map a function over a list and concatenate the results with
+    [`concatStrings`](#custom-anchor).
+
+    ## `concatStrings` in a heading
+  */
+  concatMapStrings = f: list: concatStrings (map f list);
+}
@@ -0,0 +1,36 @@
+{
+  /**
+    Create a thing.
+  */
+  mkThing =
+    {
+      /** Name of the thing. */
+      name,
+      /** Version of the thing. */
+      version ? "1.0",
+      extraArgs ? { },
+      ...
+    }:
+    { inherit name version; } // extraArgs;
+
+  /**
+    Create another thing.
+
+    # Arguments
+
+    `args`
+    : Arguments of the thing, see `mkThing`.
+  */
+  mkOtherThing =
+    {
+      /** Not rendered again, the arguments are documented above. */
+      name,
+    }:
+    mkThing { inherit name; };
+
+  /**
+    Curried arguments cannot be documented by doc-comments, and functions
+    without documented arguments do not list them.
+  */
+  add = a: b: a + b;
+}
@@ -0,0 +1,48 @@
+{
+  /**
+    Concatenate a list of strings with a separator between each element.
+
+    # Inputs
+
+    `sep`
+    : Separator to add between elements
+
+    `list`
+    : List of strings
+
+      Empty lists result in an empty string.
+
+    # Type
+
+    ```
+    concatStringsSep :: string -> [string] -> string
+    ```
+
+    # Examples {#ex-concatStringsSep}
+
+    :::{.example}
+    ## `concatStringsSep` usage example
+
+    ```nix
+    concatStringsSep "/" ["usr" "local" "bin"]
+    => "usr/local/bin"
+    ```
+
+    ```
+    # Not a heading
+    concatStringsSep "" []
+    => ""
+    ```
+    :::
+
+    # Notes
+
+    Also see `concatStrings`.
+  */
+  concatStringsSep = sep: list: builtins.concatStringsSep sep list;
+
+  /**
+    A description without sections.
+  */
+  noSections = null;
+}
@@ -0,0 +1,44 @@
+{
+  /**
+    Add two numbers.
+
+    # Examples
+
+    ```nix
+    add 1 2
+    => 3
+
+    add 1 (2
+    => 3
+    ```
+
+    Results need not be Nix, and neither does code which is not marked as such:
+
+    ```nix
+    add
+    => <LAMBDA>
+    ```
+
+    ```
+    add 1 ]
+    ```
+
+    Bindings are fine too:
+
+    ```nix title="configuration.nix"
+    services.adder.enable = true;
+    services.adder.offset = add 1 2;
+    ```
+  */
+  add = a: b: a + b;
+
+  /* Multiply two numbers.
+
+     Example:
+       mul 2 3
+       => 6
+       mul 2 { a = 3; }.a;
+       => 6
+  */
+  mul = a: b: a * b;
+}
@@ -0,0 +1,57 @@
+{
+  /**
+    Concatenate.
+
+    # Examples
+
+    ```nix
+    concatStrings [ "foo" "bar" ]
+    => "foobar"
+
+    concatStrings
+      [ "a" ]
+    => "a"
+    ```
+  */
+  concatStrings = x: x;
+
+  /* Map attrs.
+
+     Example:
+       mapAttrs (name: value: name + "-" + value)
+          { x = "a"; y = "b"; }
+       => { x = "x-a"; y = "y-b"; }
+  */
+  mapAttrs = f: s: s;
+
+  /**
+    # Examples
+
+    ```nix
+    foo 1
+    => { a = 1; ... }
+    ```
+
+    ```nix
+    let x = 1; in x
+    ```
+
+    ```nix
+    => true
+    ```
+
+    ```console
+    $ nix eval
+    ```
+  */
+  bad = x: x;
+
+  /**
+    # Examples
+
+    ```nix
+    unclosed 1
+    => 1
+  */
+  unclosed = x: x;
+}
@@ -0,0 +1,21 @@
+{ lib }:
+
+let
+  callLibs = file: import file { inherit lib; };
+in
+{
+  /** String manipulation functions */
+  strings = import ./strings.nix { inherit lib; };
+
+  /**
+    List manipulation
+    functions
+
+    Only the first paragraph of a comment is the heading of its category.
+  */
+  # Directories are imported through their default.nix
+  lists = callLibs ./lists;
+
+  # Bindings which don't import a file are not categories
+  version = "1.0";
+}
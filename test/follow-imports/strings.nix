@@ -0,0 +1,8 @@
+/**
+  Functions for working with strings.
+*/
+{ lib }:
+{
+  /** Concatenate a list of strings. */
+  concatStrings = builtins.concatStringsSep "";
+}
@@ -0,0 +1,19 @@
+{
+  /**
+    Greet the world, with the same examples as the inherited entries.
+
+    # Examples
+
+    ```nix
+    greet "world"
+    => "hello world"
+    ```
+
+    ```nix
+    greet (
+    ```
+  */
+  hello = "hello world";
+
+  inherit (import ./inherit-examples/greetings.nix { }) greet broken;
+}
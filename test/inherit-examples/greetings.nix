@@ -0,0 +1,26 @@
+{ }:
+
+{
+  /**
+    Greet someone.
+
+    # Examples
+
+    ```nix
+    greet "world"
+    => "hello world"
+    ```
+  */
+  greet = name: "hello ${name}";
+
+  /**
+    Has an example with a syntax error.
+
+    # Examples
+
+    ```nix
+    greet (
+    ```
+  */
+  broken = x: x;
+}
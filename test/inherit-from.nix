@@ -0,0 +1,41 @@
+{ lib }:
+
+let
+  helpers = {
+    /** Documented in a let-bound attribute set. */
+    letBound = a: a;
+
+    nested = {
+      /** Documented in a nested attribute set. */
+      selected = a: a;
+    };
+
+    /** Inherited from a parenthesized source. */
+    parenthesized = a: a;
+
+    /** Inherited from a source spanning several lines. */
+    multiline = a: a;
+
+    /* not inherited */
+    unused = a: a;
+  };
+
+  strings = import ./inherit-from/strings.nix { inherit lib; };
+in {
+  inherit (helpers) letBound;
+  inherit (helpers.nested) selected;
+  inherit ({
+    /** Documented in a literal attribute set. */
+    literal = a: a;
+  }) literal;
+  inherit ((helpers)) parenthesized;
+  inherit (
+    helpers
+  ) multiline;
+  inherit (strings) imported;
+  inherit (import ./inherit-from/strings.nix { inherit lib; }) importedDirectly;
+
+  # Sources which cannot be resolved statically are skipped.
+  inherit (lib.strings) concatStrings;
+  inherit (helpers) missing;
+}
@@ -0,0 +1,11 @@
+{ lib }:
+
+let
+  /** Defined in a let-binding of the imported file. */
+  imported = s: s;
+in {
+  inherit imported;
+
+  /* Defined in the imported file. */
+  importedDirectly = s: s;
+}
@@ -0,0 +1,89 @@
+{ lib }:
+
+{
+  /**
+    Documents all of its arguments.
+
+    # Arguments
+
+    `a`
+    : The first argument
+
+    `b`
+    : The second argument
+  */
+  correct = a: b: a;
+
+  /**
+    Documents an argument which does not exist, and misses another one.
+
+    # Arguments
+
+    `a`
+    : The first argument
+
+    `c`
+    : Not an argument
+  */
+  unknown = a: b: a;
+
+  /**
+    Documents its arguments in the wrong order.
+
+    # Arguments
+
+    `b`
+    : The second argument
+
+    `a`
+    : The first argument
+  */
+  order = a: b: a;
+
+  /** Documents some of its pattern arguments. */
+  pattern = {
+    /** The documented argument */
+    documented,
+    undocumented,
+  }: documented;
+
+  /* Documents some arguments with legacy comments. */
+  legacy =
+    # The documented argument
+    documented:
+    undocumented: documented;
+
+  /** Does not document arguments, which is reported by coverage instead. */
+  ignored = a: b: a;
+
+  /**
+    # Type
+
+    ```
+    validType :: { name :: string; ... } -> [a] -> attrsOf (a | null)
+    ```
+  */
+  validType = args: list: { };
+
+  /* Type: invalidType = string -> string */
+  invalidType = s: s;
+
+  /* Type: otherName :: string -> string */
+  mismatchedName = s: s;
+
+  /* Type: tooManyArguments :: string -> string */
+  tooManyArguments = a: b: a;
+
+  /** Binds a function which is not documented by nixdoc, and not linted. */
+  nested =
+    let
+      /**
+        # Arguments
+
+        `c`
+        : Not an argument
+      */
+      inner = a: a;
+    in
+    inner;
+}
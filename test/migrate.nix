@@ -0,0 +1,59 @@
+{ lib }:
+let
+  /* Apply a function to a value. */
+  apply = f: x: f x;
+in
+{
+  inherit apply;
+
+  /* Filter an attribute set by removing all attributes for which the
+     given predicate return false.
+
+     Example:
+       filterAttrs (n: v: n == "foo") { foo = 1; bar = 2; }
+       => { foo = 1; }
+
+     Type:
+       filterAttrs :: (String -> Any -> Bool) -> AttrSet -> AttrSet
+  */
+  filterAttrs =
+    # Predicate taking an attribute name and an attribute value, which returns `true` to include the attribute or `false` to exclude the attribute.
+    pred:
+    # The attribute set to filter
+    # (which is not modified)
+    set:
+    lib.listToAttrs (lib.concatMap (name: let v = set.${name}; in if pred name v then [(lib.nameValuePair name v)] else []) (lib.attrNames set));
+
+  /* Add two numbers. Type: add :: { a :: int; b :: int; } -> int */
+  add = {
+    # The first number to add.
+    a,
+    /** The second number to add. */
+    b,
+  }: a + b;
+
+  /**
+    Already migrated.
+  */
+  id = x: x;
+
+  /* Deprecated, kept as is apart from its comment. */
+  # TODO: remove
+  old = x: x;
+
+  # Line comments are no doc comments.
+  internal = 1;
+
+  /* Build a configuration. */
+  mkConfig = name:
+    let
+      /* Not documented, bindings in function bodies are left as they are.
+         Example: settings => { }
+      */
+      settings = { inherit name; };
+    in
+    {
+      /* Neither are bindings of returned attribute sets. */
+      config = settings;
+    };
+}
@@ -0,0 +1,44 @@
+{ lib }:
+
+let
+  /** Concatenate a list of strings. */
+  concatStrings = builtins.concatStringsSep "";
+in {
+  /**
+    String helpers.
+
+    # Note
+
+    Nested entries are rendered one heading level deeper.
+  */
+  strings = {
+    inherit concatStrings;
+
+    /** Prepend a prefix to a string. */
+    addPrefix = prefix: str: prefix + str;
+
+    escape = {
+      /** Escape a string for the shell. */
+      shell = s: s;
+
+      beyond = {
+        /** This is beyond the maximum depth. */
+        hidden = null;
+      };
+    };
+  };
+
+  # not documented, but its children are
+  paths = {
+    /* Join two paths. */
+    join = a: b: a + "/" + b;
+
+    deeply.nested = {
+      /** Nested attrpaths count as a single level. */
+      found = null;
+    };
+  };
+
+  /** An attrpath binding is named after its full path. */
+  attr.path = null;
+}
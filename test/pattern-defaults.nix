@@ -0,0 +1,30 @@
+{
+  /**
+    Create a package.
+  */
+  mkPackage =
+    args@{
+      /** Name of the package. */
+      pname,
+      /** Version of the package, defaulting to an unstable version. */
+      version ? "unstable-${
+        toString 0
+      }",
+      /** Whether to run the tests. */
+      doCheck ? true,
+      ...
+    }:
+    args;
+
+  /**
+    Wrap a package.
+  */
+  wrap =
+    {
+      /** The package to wrap. */
+      package,
+      /** Flags passed to the wrapper. */
+      flags ? [ ],
+    }@attrs:
+    attrs;
+}
@@ -0,0 +1,27 @@
+/**
+  Functions on lists.
+*/
+{
+  /**
+    Concatenate the strings of a list, like `strings.concatStrings`, or
+    [its example](#function-library-example-lib.lists.flatten).
+
+    ::: {.example #ex-concat}
+    # Concatenating strings
+
+    ```nix
+    concat [ "a" "b" ]
+    => "ab"
+    ```
+    :::
+  */
+  concat = list: builtins.concatStringsSep "" list;
+
+  /* Flatten a list of lists.
+
+     Example:
+       flatten [ [ 1 ] [ 2 ] ]
+       => [ 1 2 ]
+  */
+  flatten = lists: builtins.concatLists lists;
+}